@@ -0,0 +1,41 @@
+// benches/render.rs
+//
+// Frame time should not depend on how many rows the table has, only on how
+// many fit on screen.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ratatui::backend::TestBackend;
+use ratatui::Terminal;
+
+use fastdata::tui_app::ColumnWidth;
+use fastdata::{TableData, TuiApp, VirtualTable};
+
+const COLUMNS: usize = 8;
+
+
+fn make_table(rows: usize) -> TableData {
+    let headers = (0..COLUMNS).map(|c| format!("column_{}", c)).collect();
+    let columns = (0..COLUMNS)
+        .map(|c| (0..rows).map(|r| format!("{}-{}", r, c * 7919 % 1000)).collect())
+        .collect();
+    TableData::new(headers, columns)
+}
+
+fn render(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw_ui");
+    for rows in [10_000, 100_000, 1_000_000] {
+        let mut app = TuiApp::new(VirtualTable::new(make_table(rows)));
+        app.column_widths = vec![ColumnWidth::Content; COLUMNS];
+        // Park the cursor mid-table so scrolling is part of every frame.
+        app.selected_row = rows / 2;
+        let mut terminal = Terminal::new(TestBackend::new(200, 50)).unwrap();
+
+        group.bench_with_input(BenchmarkId::from_parameter(rows), &rows, |b, _| {
+            b.iter(|| app.draw_ui(&mut terminal).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
@@ -0,0 +1,512 @@
+// src/aggregation.rs
+
+use std::collections::{HashMap, HashSet};
+
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use crate::column_type::TypedColumn;
+use crate::data_loader::TableData;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, EnumIter, PartialOrd, Ord)]
+pub enum AggregationFunction {
+    Count,
+    NonNullCount,
+    NullCount,
+    UniqueCount,
+    Sum,
+    Mean,
+    Min,
+    Max,
+    Median,
+    Mode,
+    StdDev,
+    Variance,
+    P25,
+    P75,
+    P90,
+    P99,
+}
+
+impl AggregationFunction {
+    /// Looks a function up by its name, ignoring case, as in `sum` or `p90`.
+    pub fn parse(name: &str) -> Option<Self> {
+        AggregationFunction::iter().find(|agg| format!("{:?}", agg).eq_ignore_ascii_case(name))
+    }
+
+    /// Whether the function works on cells read as numbers.
+    fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            AggregationFunction::Sum
+                | AggregationFunction::Mean
+                | AggregationFunction::Median
+                | AggregationFunction::StdDev
+                | AggregationFunction::Variance
+                | AggregationFunction::P25
+                | AggregationFunction::P75
+                | AggregationFunction::P90
+                | AggregationFunction::P99
+        )
+    }
+
+    fn quantile(self) -> Option<f64> {
+        match self {
+            AggregationFunction::P25 => Some(0.25),
+            AggregationFunction::Median => Some(0.5),
+            AggregationFunction::P75 => Some(0.75),
+            AggregationFunction::P90 => Some(0.9),
+            AggregationFunction::P99 => Some(0.99),
+            _ => None,
+        }
+    }
+}
+
+
+/// What to do with cells that cannot be read as the values an aggregation needs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnparseablePolicy {
+    /// Leave them out and report how many were left out.
+    #[default]
+    Skip,
+    /// Give no result at all.
+    Fail,
+}
+
+impl UnparseablePolicy {
+    pub fn toggle(self) -> Self {
+        match self {
+            UnparseablePolicy::Skip => UnparseablePolicy::Fail,
+            UnparseablePolicy::Fail => UnparseablePolicy::Skip,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            UnparseablePolicy::Skip => "skip unparseable",
+            UnparseablePolicy::Fail => "fail on unparseable",
+        }
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AggregationResult {
+    /// `None` when there was nothing to aggregate.
+    Value { value: Option<String>, skipped: usize },
+    /// Unparseable cells were found under `UnparseablePolicy::Fail`.
+    Failed { unparseable: usize },
+}
+
+impl AggregationResult {
+    pub fn display(&self) -> String {
+        match self {
+            AggregationResult::Value { value, skipped: 0 } => {
+                value.clone().unwrap_or_else(|| "-".to_string())
+            }
+            AggregationResult::Value { value, skipped } => format!(
+                "{} ({} skipped)",
+                value.as_deref().unwrap_or("-"),
+                skipped
+            ),
+            AggregationResult::Failed { unparseable } => format!("{} unparseable", unparseable),
+        }
+    }
+}
+
+
+/// Computes `aggs` over the given rows of a column.
+pub fn aggregate_column(
+    data: &TableData,
+    col: usize,
+    rows: impl Iterator<Item = usize>,
+    aggs: &[AggregationFunction],
+    policy: UnparseablePolicy,
+) -> HashMap<AggregationFunction, AggregationResult> {
+    let sample = ColumnSample::new(data, col, rows);
+    let numbers = aggs.iter().any(|agg| agg.is_numeric()).then(|| {
+        let mut numbers = sample.numbers();
+        if aggs.iter().any(|agg| agg.quantile().is_some()) {
+            numbers.values.sort_by(f64::total_cmp);
+        }
+        numbers
+    });
+
+    aggs.iter()
+        .map(|&agg| {
+            let result = match agg {
+                AggregationFunction::Count => exact(sample.rows.to_string()),
+                AggregationFunction::NullCount => exact(sample.missing.to_string()),
+                AggregationFunction::NonNullCount => {
+                    exact((sample.rows - sample.missing).to_string())
+                }
+                AggregationFunction::UniqueCount => {
+                    let unique: HashSet<&str> = sample.non_missing().collect();
+                    exact(unique.len().to_string())
+                }
+                AggregationFunction::Mode => AggregationResult::Value {
+                    value: sample.mode().map(str::to_string),
+                    skipped: 0,
+                },
+                AggregationFunction::Min | AggregationFunction::Max => {
+                    checked(sample.unparseable.len(), policy, || {
+                        sample
+                            .extreme(agg == AggregationFunction::Max)
+                            .map(str::to_string)
+                    })
+                }
+                _ => {
+                    let numbers = numbers.as_ref().expect("numbers are read for numeric aggregations");
+                    checked(numbers.skipped, policy, || numeric(agg, numbers))
+                }
+            };
+            (agg, result)
+        })
+        .collect()
+}
+
+/// Results by data column as a table with a row per column and a column per
+/// function. Functions not computed for a column, or that failed, are null.
+pub fn results_table(
+    data: &TableData,
+    results: &HashMap<usize, HashMap<AggregationFunction, AggregationResult>>,
+) -> TableData {
+    let mut functions: Vec<AggregationFunction> = results
+        .values()
+        .flat_map(|aggs| aggs.keys().copied())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    functions.sort();
+    let mut columns: Vec<usize> = results.keys().copied().collect();
+    columns.sort();
+
+    let headers = std::iter::once("Column".to_string())
+        .chain(functions.iter().map(|agg| format!("{:?}", agg)))
+        .collect();
+    let mut cells = vec![columns.iter().map(|&col| data.headers[col].clone()).collect()];
+    let mut nulls = vec![Vec::new()];
+    for agg in &functions {
+        let values: Vec<Option<String>> = columns
+            .iter()
+            .map(|col| match results[col].get(agg) {
+                Some(AggregationResult::Value { value, .. }) => value.clone(),
+                _ => None,
+            })
+            .collect();
+        nulls.push(values.iter().map(Option::is_none).collect());
+        cells.push(values.into_iter().map(Option::unwrap_or_default).collect());
+    }
+    TableData::with_nulls(headers, cells, nulls)
+}
+
+/// A profile of the given data columns over the given rows, a row per
+/// column: its type, counts, range, central values and a sample value.
+/// Numeric statistics are left null for columns that are not numeric.
+pub fn describe_table(
+    data: &TableData,
+    columns: &[usize],
+    rows: &[usize],
+    policy: UnparseablePolicy,
+) -> TableData {
+    use AggregationFunction::*;
+    const STATISTICS: [AggregationFunction; 9] =
+        [NonNullCount, NullCount, UniqueCount, Min, Max, Mean, Median, StdDev, Mode];
+
+    let headers = ["Column", "Type"]
+        .into_iter()
+        .map(str::to_string)
+        .chain(STATISTICS.iter().map(|agg| format!("{:?}", agg)))
+        .chain(std::iter::once("Sample".to_string()))
+        .collect();
+    let mut values: Vec<Vec<Option<String>>> = vec![Vec::new(); STATISTICS.len() + 3];
+    for &col in columns {
+        let column_type = data.column_type(col);
+        let aggs: Vec<AggregationFunction> = STATISTICS
+            .into_iter()
+            .filter(|agg| column_type.is_numeric() || !agg.is_numeric())
+            .collect();
+        let results = aggregate_column(data, col, rows.iter().copied(), &aggs, policy);
+        let sample = rows
+            .iter()
+            .find(|&&row| !data.is_null(col, row) && !data.columns[col][row].trim().is_empty())
+            .map(|&row| data.columns[col][row].clone());
+
+        values[0].push(Some(data.headers[col].clone()));
+        values[1].push(Some(column_type.label().to_string()));
+        for (i, agg) in STATISTICS.iter().enumerate() {
+            values[i + 2].push(match results.get(agg) {
+                Some(AggregationResult::Value { value, .. }) => value.clone(),
+                _ => None,
+            });
+        }
+        values[STATISTICS.len() + 2].push(sample);
+    }
+
+    let nulls = values
+        .iter()
+        .map(|column| column.iter().map(Option::is_none).collect())
+        .collect();
+    let cells = values
+        .into_iter()
+        .map(|column| column.into_iter().map(Option::unwrap_or_default).collect())
+        .collect();
+    TableData::with_nulls(headers, cells, nulls)
+}
+
+fn exact(value: String) -> AggregationResult {
+    AggregationResult::Value { value: Some(value), skipped: 0 }
+}
+
+fn checked(
+    unparseable: usize,
+    policy: UnparseablePolicy,
+    compute: impl FnOnce() -> Option<String>,
+) -> AggregationResult {
+    if unparseable > 0 && policy == UnparseablePolicy::Fail {
+        AggregationResult::Failed { unparseable }
+    } else {
+        AggregationResult::Value { value: compute(), skipped: unparseable }
+    }
+}
+
+fn numeric(agg: AggregationFunction, numbers: &Numbers) -> Option<String> {
+    let values = &numbers.values;
+    if values.is_empty() {
+        return None;
+    }
+    let count = values.len() as f64;
+    let mean = || values.iter().sum::<f64>() / count;
+    // Sample variance, as spreadsheets and pandas report by default.
+    let variance = || {
+        (values.len() > 1).then(|| {
+            let mean = mean();
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (count - 1.0)
+        })
+    };
+
+    match agg {
+        AggregationFunction::Sum => Some(match numbers.integer_sum {
+            Some(sum) => sum.to_string(),
+            None => format_number(values.iter().sum()),
+        }),
+        AggregationFunction::Mean => Some(format_number(mean())),
+        AggregationFunction::Variance => variance().map(format_number),
+        AggregationFunction::StdDev => variance().map(|v| format_number(v.sqrt())),
+        _ => agg.quantile().map(|q| format_number(quantile(values, q))),
+    }
+}
+
+/// Linear interpolation between the closest ranks of sorted values.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let position = q * (sorted.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64)
+}
+
+/// Whole numbers without a fraction, others to at most six decimals.
+pub fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{:.0}", value)
+    } else {
+        let text = format!("{:.6}", value);
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+
+/// The cells of one column over a set of rows, sorted out for aggregating.
+struct ColumnSample<'a> {
+    cells: &'a [String],
+    typed: &'a TypedColumn,
+    rows: usize,
+    /// Null or empty cells.
+    missing: usize,
+    /// Rows whose cell holds a value of the column's type.
+    present: Vec<usize>,
+    /// Rows whose non-empty cell could not be read as the column's type.
+    unparseable: Vec<usize>,
+}
+
+impl<'a> ColumnSample<'a> {
+    fn new(data: &'a TableData, col: usize, rows: impl Iterator<Item = usize>) -> Self {
+        let cells = &data.columns[col];
+        let typed = &data.typed[col];
+        let mut sample = ColumnSample {
+            cells,
+            typed,
+            rows: 0,
+            missing: 0,
+            present: Vec::new(),
+            unparseable: Vec::new(),
+        };
+        for row in rows {
+            sample.rows += 1;
+            if data.is_null(col, row) || cells[row].trim().is_empty() {
+                sample.missing += 1;
+            } else if typed.has_value(row) {
+                sample.present.push(row);
+            } else {
+                sample.unparseable.push(row);
+            }
+        }
+        sample
+    }
+
+    /// Raw text of every cell that is not null or empty.
+    fn non_missing(&self) -> impl Iterator<Item = &'a str> + '_ {
+        let cells = self.cells;
+        self.present
+            .iter()
+            .chain(&self.unparseable)
+            .map(move |&row| cells[row].as_str())
+    }
+
+    /// The most common value; the first one seen wins a tie.
+    fn mode(&self) -> Option<&'a str> {
+        let mut counts: HashMap<&str, (usize, usize)> = HashMap::new();
+        for (order, cell) in self.non_missing().enumerate() {
+            counts.entry(cell).or_insert((0, order)).0 += 1;
+        }
+        counts
+            .into_iter()
+            .max_by(|(_, (a_count, a_order)), (_, (b_count, b_order))| {
+                a_count.cmp(b_count).then(b_order.cmp(a_order))
+            })
+            .map(|(cell, _)| cell)
+    }
+
+    /// Smallest or largest value by the column's type, as it was written.
+    fn extreme(&self, largest: bool) -> Option<&'a str> {
+        let compare = |a: &&usize, b: &&usize| self.typed.compare(self.cells, **a, **b);
+        let row = if largest {
+            self.present.iter().max_by(compare)
+        } else {
+            self.present.iter().min_by(compare)
+        };
+        row.map(|&row| self.cells[row].as_str())
+    }
+
+    /// The present cells read as numbers. Columns of other types have their
+    /// text parsed, and cells that are not numbers count as skipped.
+    fn numbers(&self) -> Numbers {
+        let mut numbers = Numbers {
+            values: Vec::with_capacity(self.present.len()),
+            skipped: self.unparseable.len(),
+            integer_sum: None,
+        };
+        match self.typed {
+            TypedColumn::Integer(values) => {
+                let mut sum = Some(0i64);
+                for &row in &self.present {
+                    let value = values[row].expect("present rows have a value");
+                    sum = sum.and_then(|s| s.checked_add(value));
+                    numbers.values.push(value as f64);
+                }
+                // Past the range of i64 the total is still worth showing, if rounded.
+                numbers.integer_sum = sum;
+            }
+            TypedColumn::Float(values) => {
+                numbers
+                    .values
+                    .extend(self.present.iter().filter_map(|&row| values[row]));
+            }
+            _ => {
+                for &row in &self.present {
+                    match self.cells[row].trim().parse::<f64>() {
+                        Ok(value) if value.is_finite() => numbers.values.push(value),
+                        _ => numbers.skipped += 1,
+                    }
+                }
+            }
+        }
+        numbers
+    }
+}
+
+struct Numbers {
+    values: Vec<f64>,
+    skipped: usize,
+    /// Exact total for integer columns, unless it overflowed.
+    integer_sum: Option<i64>,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use AggregationFunction::*;
+
+    fn column(values: &[&str]) -> TableData {
+        let cells = values.iter().map(|v| v.to_string()).collect();
+        TableData::new(vec!["x".to_string()], vec![cells])
+    }
+
+    fn aggregate(values: &[&str], agg: AggregationFunction, policy: UnparseablePolicy) -> String {
+        let data = column(values);
+        let results = aggregate_column(&data, 0, 0..values.len(), &[agg], policy);
+        results[&agg].display()
+    }
+
+    fn value(values: &[&str], agg: AggregationFunction) -> String {
+        aggregate(values, agg, UnparseablePolicy::Skip)
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(value(&["3", "1", "2"], Median), "2");
+        assert_eq!(value(&["4", "1", "3", "2"], Median), "2.5");
+    }
+
+    #[test]
+    fn percentiles_interpolate_linearly_between_ranks() {
+        // Positions are q * (n - 1), as numpy and spreadsheets' PERCENTILE.INC do.
+        let values = ["1", "2", "3", "4", "5"];
+        assert_eq!(value(&values, P25), "2");
+        assert_eq!(value(&values, P75), "4");
+        assert_eq!(value(&values, P90), "4.6");
+        assert_eq!(value(&values, P99), "4.96");
+        assert_eq!(value(&["10"], P90), "10");
+    }
+
+    #[test]
+    fn variance_and_stddev_are_sample_statistics() {
+        let values = ["2", "4", "4", "4", "5", "5", "7", "9"];
+        assert_eq!(value(&values, Variance), "4.571429");
+        assert_eq!(value(&values, StdDev), "2.13809");
+        assert_eq!(value(&["5"], Variance), "-");
+    }
+
+    #[test]
+    fn mode_ties_go_to_the_first_value_seen() {
+        assert_eq!(value(&["b", "a", "a", "b", "c"], Mode), "b");
+        assert_eq!(value(&["", "c", "", "c", "d"], Mode), "c");
+    }
+
+    #[test]
+    fn counts_tell_missing_from_present() {
+        let values = ["a", "", "a", "b"];
+        assert_eq!(value(&values, Count), "4");
+        assert_eq!(value(&values, NullCount), "1");
+        assert_eq!(value(&values, NonNullCount), "3");
+        assert_eq!(value(&values, UniqueCount), "2");
+    }
+
+    #[test]
+    fn unparseable_cells_are_skipped_or_fail() {
+        let values = ["1", "x", "3.5"];
+        assert_eq!(value(&values, Sum), "4.5 (1 skipped)");
+        assert_eq!(value(&values, Mean), "2.25 (1 skipped)");
+        assert_eq!(aggregate(&values, Sum, UnparseablePolicy::Fail), "1 unparseable");
+        assert_eq!(aggregate(&["1", "2"], Sum, UnparseablePolicy::Fail), "3");
+    }
+
+    #[test]
+    fn function_names_parse_ignoring_case() {
+        assert_eq!(AggregationFunction::parse("p90"), Some(P90));
+        assert_eq!(AggregationFunction::parse("STDDEV"), Some(StdDev));
+        assert_eq!(AggregationFunction::parse("avg"), None);
+    }
+}
@@ -0,0 +1,118 @@
+// src/background_loader.rs
+
+use std::error::Error;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+use crate::data_loader::TableData;
+
+pub enum LoadMessage {
+    /// More rows; the chunk's headers are every column seen so far.
+    Chunk(TableData),
+    Finished,
+    Failed(String),
+}
+
+/// A load running on its own thread, handing rows to the UI as they are parsed.
+pub struct BackgroundLoad {
+    receiver: Receiver<LoadMessage>,
+    bytes_read: Arc<AtomicU64>,
+    cancelled: Arc<AtomicBool>,
+    pub total_bytes: Option<u64>,
+}
+
+impl BackgroundLoad {
+    /// Runs `job` on a new thread. The job reports bytes consumed through the
+    /// counter it is given and hands each parsed chunk to the callback, which
+    /// sends it and returns false once the load has been cancelled.
+    pub fn spawn<F>(total_bytes: Option<u64>, job: F) -> Self
+    where
+        F: FnOnce(Arc<AtomicU64>, &mut dyn FnMut(TableData) -> bool) -> Result<(), Box<dyn Error>>
+            + Send
+            + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let bytes_read = Arc::new(AtomicU64::new(0));
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let thread_bytes_read = Arc::clone(&bytes_read);
+        let thread_cancelled = Arc::clone(&cancelled);
+        thread::spawn(move || {
+            // A chunk parsed before the cancel still goes out, so what was read is kept.
+            let mut on_chunk = |chunk: TableData| {
+                sender.send(LoadMessage::Chunk(chunk)).is_ok()
+                    && !thread_cancelled.load(Ordering::Relaxed)
+            };
+            let message = match job(thread_bytes_read, &mut on_chunk) {
+                Ok(()) => LoadMessage::Finished,
+                Err(e) => LoadMessage::Failed(e.to_string()),
+            };
+            let _ = sender.send(message);
+        });
+
+        BackgroundLoad {
+            receiver,
+            bytes_read,
+            cancelled,
+            total_bytes,
+        }
+    }
+
+    /// Asks the loader to stop after the chunk it is working on.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+
+    /// Everything the loader has sent since the last call, without blocking.
+    pub fn poll(&self) -> Vec<LoadMessage> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+
+/// Counts the bytes passing through a reader, for progress reporting.
+pub struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: Read> CountingReader<R> {
+    pub fn new(inner: R, count: Arc<AtomicU64>) -> Self {
+        CountingReader { inner, count }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
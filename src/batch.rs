@@ -0,0 +1,256 @@
+// src/batch.rs
+//
+// Runs the sort, filter and aggregation of the interactive sheets from the
+// command line, for scripts:
+//
+//     fastdata --filter 'country == "FR"' --sort amount:desc --out top.csv sales.parquet
+//     fastdata --agg amount:sum,mean --agg id:count sales.csv
+//
+// The filter applies first, then the sort. With `--agg` the output is the
+// aggregation results, a row per column, rather than the rows themselves.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, Write};
+
+use crate::aggregation::{
+    aggregate_column, results_table, AggregationFunction, AggregationResult, UnparseablePolicy,
+};
+use crate::data_loader::TableData;
+use crate::data_writer::{get_writer, writer_for_path};
+use crate::filter::Filter;
+use crate::sort::{Collation, SortKey};
+use crate::virtual_table::VirtualTable;
+
+
+/// Command line options that make fastdata run without the TUI.
+#[derive(Default)]
+pub struct BatchOptions {
+    pub filter: Option<String>,
+    /// Columns to sort by, most significant first, and whether ascending.
+    pub sort: Vec<(String, bool)>,
+    pub collation: Collation,
+    pub aggregations: Vec<(String, Vec<AggregationFunction>)>,
+    pub policy: UnparseablePolicy,
+    /// File to write to; standard output when unset.
+    pub output: Option<String>,
+    /// Format of the output, by extension, when the file name does not tell.
+    pub format: Option<String>,
+}
+
+impl BatchOptions {
+    /// Whether any option asks for batch mode.
+    pub fn is_set(&self) -> bool {
+        self.filter.is_some()
+            || !self.sort.is_empty()
+            || !self.aggregations.is_empty()
+            || self.output.is_some()
+            || self.format.is_some()
+    }
+
+    /// Takes the value of a batch option; false when `option` is not one.
+    pub fn set(&mut self, option: &str, value: &str) -> Result<bool, Box<dyn Error>> {
+        match option {
+            "--filter" => self.filter = Some(value.to_string()),
+            "--sort" => {
+                for spec in value.split(',') {
+                    self.sort.push(parse_sort_key(spec)?);
+                }
+            }
+            "--collation" => {
+                self.collation = match value {
+                    "binary" => Collation::Binary,
+                    "natural" => Collation::Natural,
+                    "nocase" => Collation::CaseInsensitive,
+                    _ => {
+                        return Err(format!(
+                            "Unknown collation '{}', expected binary, natural or nocase",
+                            value
+                        )
+                        .into())
+                    }
+                }
+            }
+            "--agg" => self.aggregations.push(parse_aggregations(value)?),
+            "--out" => self.output = Some(value.to_string()),
+            "--format" => self.format = Some(value.to_string()),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+
+/// `amount`, `amount:asc` or `amount:desc`.
+fn parse_sort_key(spec: &str) -> Result<(String, bool), Box<dyn Error>> {
+    match spec.rsplit_once(':') {
+        Some((column, "asc")) => Ok((column.to_string(), true)),
+        Some((column, "desc")) => Ok((column.to_string(), false)),
+        Some((_, direction)) => {
+            Err(format!("Sort direction must be asc or desc, not '{}'", direction).into())
+        }
+        None => Ok((spec.to_string(), true)),
+    }
+}
+
+/// `amount:sum,mean`.
+fn parse_aggregations(
+    spec: &str,
+) -> Result<(String, Vec<AggregationFunction>), Box<dyn Error>> {
+    let (column, names) = spec
+        .rsplit_once(':')
+        .ok_or_else(|| format!("Expected column:function,..., not '{}'", spec))?;
+    let functions = names
+        .split(',')
+        .map(|name| {
+            AggregationFunction::parse(name.trim())
+                .ok_or_else(|| format!("Unknown aggregation '{}'", name).into())
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    Ok((column.to_string(), functions))
+}
+
+
+/// Filters, sorts and aggregates `data` as the options say and writes the
+/// result out.
+pub fn run(data: TableData, options: &BatchOptions) -> Result<(), Box<dyn Error>> {
+    let mut table = VirtualTable::new(data);
+
+    if let Some(text) = &options.filter {
+        let filter = Filter::parse(text, &table.data)?;
+        let rows = filter.matching_rows(&table.data, 0..table.data.num_rows());
+        table.set_rows(rows);
+    }
+
+    if !options.sort.is_empty() {
+        let keys = options
+            .sort
+            .iter()
+            .map(|(name, ascending)| {
+                Ok(SortKey {
+                    column: column_index(&table.data, name)?,
+                    ascending: *ascending,
+                })
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+        table.sort_by(&keys, options.collation);
+    }
+
+    if !options.aggregations.is_empty() {
+        table = VirtualTable::new(aggregate(&table, options)?);
+    }
+
+    let writer = match (&options.format, &options.output) {
+        (Some(format), _) => get_writer(format)?,
+        (None, Some(path)) => writer_for_path(path)?,
+        (None, None) => get_writer("csv")?,
+    };
+    match &options.output {
+        Some(path) => writer.save(&table, path),
+        None => {
+            let mut stdout = io::stdout().lock();
+            writer.write(&table, &mut stdout)?;
+            stdout.flush()?;
+            Ok(())
+        }
+    }
+}
+
+fn aggregate(table: &VirtualTable, options: &BatchOptions) -> Result<TableData, Box<dyn Error>> {
+    let mut results: HashMap<usize, HashMap<AggregationFunction, AggregationResult>> =
+        HashMap::new();
+    for (name, functions) in &options.aggregations {
+        let col = column_index(&table.data, name)?;
+        let column = aggregate_column(
+            &table.data,
+            col,
+            table.rows().iter().copied(),
+            functions,
+            options.policy,
+        );
+        for (agg, result) in &column {
+            if let AggregationResult::Failed { unparseable } = result {
+                return Err(format!("{:?}({}): {} unparseable cells", agg, name, unparseable).into());
+            }
+        }
+        results.entry(col).or_default().extend(column);
+    }
+    Ok(results_table(&table.data, &results))
+}
+
+fn column_index(data: &TableData, name: &str) -> Result<usize, Box<dyn Error>> {
+    data.headers
+        .iter()
+        .position(|header| header == name)
+        .ok_or_else(|| format!("No column named '{}'", name).into())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::Source;
+
+    const SALES: &str = "id,country,amount\n1,FR,10\n2,DE,x\n3,FR,30\n4,FR,5\n";
+
+    fn options(args: &[(&str, &str)]) -> BatchOptions {
+        let mut options = BatchOptions::default();
+        for (option, value) in args {
+            assert!(options.set(option, value).unwrap());
+        }
+        options
+    }
+
+    /// Loads SALES from a file and runs the options on it as the command
+    /// line would, returning what was written to `output`.
+    fn batch(mut options: BatchOptions, output: &str) -> Result<String, Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("sales.csv");
+        std::fs::write(&input, SALES)?;
+        let output = dir.path().join(output);
+        options.output = Some(output.to_string_lossy().into_owned());
+
+        let data = Source::open(&input.to_string_lossy(), None, None, None)?.load()?;
+        run(data, &options)?;
+        Ok(std::fs::read_to_string(output)?)
+    }
+
+    #[test]
+    fn filters_then_sorts() {
+        let args = [("--filter", "country == \"FR\""), ("--sort", "id:desc")];
+        let out = batch(options(&args), "out.csv").unwrap();
+        assert_eq!(out, "id,country,amount\n4,FR,5\n3,FR,30\n1,FR,10\n");
+    }
+
+    #[test]
+    fn output_format_follows_the_extension_or_format() {
+        let out = batch(options(&[("--filter", "id == 2")]), "out.jsonl").unwrap();
+        assert_eq!(out, "{\"id\":2,\"country\":\"DE\",\"amount\":\"x\"}\n");
+        let args = [("--filter", "id == 2"), ("--format", "tsv")];
+        let out = batch(options(&args), "out.txt").unwrap();
+        assert_eq!(out, "id\tcountry\tamount\n2\tDE\tx\n");
+    }
+
+    #[test]
+    fn aggregations_skip_or_fail_on_unparseable_cells() {
+        let args = [("--agg", "amount:sum,max"), ("--agg", "id:count")];
+        let out = batch(options(&args), "out.csv").unwrap();
+        assert_eq!(out, "Column,Count,Sum,Max\nid,4,,\namount,,45,x\n");
+
+        let mut failing = options(&[("--agg", "amount:sum")]);
+        failing.policy = UnparseablePolicy::Fail;
+        let err = batch(failing, "out.csv").unwrap_err();
+        assert_eq!(err.to_string(), "Sum(amount): 1 unparseable cells");
+    }
+
+    #[test]
+    fn bad_options_are_reported() {
+        let mut options = BatchOptions::default();
+        assert!(options.set("--sort", "amount:up").is_err());
+        assert!(options.set("--agg", "amount:total").is_err());
+        assert!(options.set("--collation", "klingon").is_err());
+        assert!(!options.set("--verbose", "").unwrap());
+        options.sort = vec![("missing".to_string(), true)];
+        assert!(batch(options, "out.csv").is_err());
+    }
+}
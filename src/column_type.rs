@@ -0,0 +1,376 @@
+// src/column_type.rs
+
+use std::cmp::Ordering;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use strum_macros::EnumIter;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Date and time layouts tried in order; RFC 3339 timestamps with an offset
+/// are handled separately and converted to UTC.
+const DATETIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+];
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, EnumIter)]
+pub enum ColumnType {
+    Integer,
+    Float,
+    Boolean,
+    Date,
+    DateTime,
+    String,
+}
+
+impl ColumnType {
+    /// Short name shown next to the column header.
+    pub fn label(self) -> &'static str {
+        match self {
+            ColumnType::Integer => "int",
+            ColumnType::Float => "float",
+            ColumnType::Boolean => "bool",
+            ColumnType::Date => "date",
+            ColumnType::DateTime => "datetime",
+            ColumnType::String => "str",
+        }
+    }
+
+    pub fn is_numeric(self) -> bool {
+        matches!(self, ColumnType::Integer | ColumnType::Float)
+    }
+
+    /// The type after this one when cycling through them by hand.
+    pub fn next(self) -> Self {
+        match self {
+            ColumnType::Integer => ColumnType::Float,
+            ColumnType::Float => ColumnType::Boolean,
+            ColumnType::Boolean => ColumnType::Date,
+            ColumnType::Date => ColumnType::DateTime,
+            ColumnType::DateTime => ColumnType::String,
+            ColumnType::String => ColumnType::Integer,
+        }
+    }
+
+    /// The narrowest type that can hold values of both.
+    pub fn join(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Integer, ColumnType::Float) | (ColumnType::Float, ColumnType::Integer) => {
+                ColumnType::Float
+            }
+            (ColumnType::Date, ColumnType::DateTime) | (ColumnType::DateTime, ColumnType::Date) => {
+                ColumnType::DateTime
+            }
+            _ => ColumnType::String,
+        }
+    }
+
+    fn accepts(self, cell: &str) -> bool {
+        match self {
+            ColumnType::Integer => parse_integer(cell).is_some(),
+            ColumnType::Float => parse_float(cell).is_some(),
+            ColumnType::Boolean => parse_boolean(cell).is_some(),
+            ColumnType::Date => parse_date(cell).is_some(),
+            ColumnType::DateTime => parse_datetime(cell).is_some(),
+            ColumnType::String => true,
+        }
+    }
+
+    /// The narrowest type a single non-empty cell fits.
+    fn detect(cell: &str) -> Self {
+        if parse_boolean(cell).is_some() {
+            ColumnType::Boolean
+        } else if parse_integer(cell).is_some() {
+            ColumnType::Integer
+        } else if parse_float(cell).is_some() {
+            ColumnType::Float
+        } else if parse_date(cell).is_some() {
+            ColumnType::Date
+        } else if parse_datetime(cell).is_some() {
+            ColumnType::DateTime
+        } else {
+            ColumnType::String
+        }
+    }
+}
+
+
+/// Infers the type of a column from its non-empty, non-null cells. Returns
+/// `None` when there are no such cells to go by.
+pub fn infer_type(cells: &[String], nulls: &[bool]) -> Option<ColumnType> {
+    let mut inferred: Option<ColumnType> = None;
+    for (row, cell) in cells.iter().enumerate() {
+        let cell = cell.trim();
+        if cell.is_empty() || nulls.get(row).copied().unwrap_or(false) {
+            continue;
+        }
+        inferred = Some(match inferred {
+            Some(ty) if ty.accepts(cell) => ty,
+            Some(ty) => ty.join(ColumnType::detect(cell)),
+            None => ColumnType::detect(cell),
+        });
+        if inferred == Some(ColumnType::String) {
+            break;
+        }
+    }
+    inferred
+}
+
+
+fn parse_integer(cell: &str) -> Option<i64> {
+    cell.parse().ok()
+}
+
+/// Rejects the `inf` and `NaN` spellings Rust accepts, which are more likely words.
+pub fn parse_float(cell: &str) -> Option<f64> {
+    if !cell.bytes().any(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    cell.parse().ok()
+}
+
+pub fn parse_boolean(cell: &str) -> Option<bool> {
+    if cell.eq_ignore_ascii_case("true") {
+        Some(true)
+    } else if cell.eq_ignore_ascii_case("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+fn parse_date(cell: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(cell, DATE_FORMAT).ok()
+}
+
+pub fn parse_datetime(cell: &str) -> Option<NaiveDateTime> {
+    DATETIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(cell, format).ok())
+        .or_else(|| DateTime::parse_from_rfc3339(cell).ok().map(|dt| dt.naive_utc()))
+        .or_else(|| parse_date(cell).and_then(|d| d.and_hms_opt(0, 0, 0)))
+}
+
+
+/// A single parsed cell, in a form that compares across integer and float
+/// columns and across date and datetime ones.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum TypedValue {
+    Number(f64),
+    Boolean(bool),
+    DateTime(NaiveDateTime),
+}
+
+
+/// Parsed values of a column. Empty, null and unparseable cells are `None`.
+#[derive(Clone, Debug)]
+pub enum TypedColumn {
+    Integer(Vec<Option<i64>>),
+    Float(Vec<Option<f64>>),
+    Boolean(Vec<Option<bool>>),
+    Date(Vec<Option<NaiveDate>>),
+    DateTime(Vec<Option<NaiveDateTime>>),
+    /// Text is used as is, so nothing is stored beyond the raw cells.
+    String,
+}
+
+impl TypedColumn {
+    /// Parses every cell as `column_type`. Also returns how many non-empty
+    /// cells did not parse, which only happens when the type was forced.
+    pub fn parse(column_type: ColumnType, cells: &[String], nulls: &[bool]) -> (Self, usize) {
+        fn values<T>(
+            cells: &[String],
+            nulls: &[bool],
+            failed: &mut usize,
+            parse: impl Fn(&str) -> Option<T>,
+        ) -> Vec<Option<T>> {
+            cells
+                .iter()
+                .enumerate()
+                .map(|(row, cell)| {
+                    let cell = cell.trim();
+                    if cell.is_empty() || nulls.get(row).copied().unwrap_or(false) {
+                        return None;
+                    }
+                    let value = parse(cell);
+                    if value.is_none() {
+                        *failed += 1;
+                    }
+                    value
+                })
+                .collect()
+        }
+
+        let mut failed = 0;
+        let column = match column_type {
+            ColumnType::Integer => {
+                TypedColumn::Integer(values(cells, nulls, &mut failed, parse_integer))
+            }
+            ColumnType::Float => TypedColumn::Float(values(cells, nulls, &mut failed, parse_float)),
+            ColumnType::Boolean => {
+                TypedColumn::Boolean(values(cells, nulls, &mut failed, parse_boolean))
+            }
+            ColumnType::Date => TypedColumn::Date(values(cells, nulls, &mut failed, parse_date)),
+            ColumnType::DateTime => {
+                TypedColumn::DateTime(values(cells, nulls, &mut failed, parse_datetime))
+            }
+            ColumnType::String => TypedColumn::String,
+        };
+        (column, failed)
+    }
+
+    /// Appends the values of another column of the same type.
+    pub fn extend(&mut self, other: TypedColumn) {
+        match (self, other) {
+            (TypedColumn::Integer(a), TypedColumn::Integer(b)) => a.extend(b),
+            (TypedColumn::Float(a), TypedColumn::Float(b)) => a.extend(b),
+            (TypedColumn::Boolean(a), TypedColumn::Boolean(b)) => a.extend(b),
+            (TypedColumn::Date(a), TypedColumn::Date(b)) => a.extend(b),
+            (TypedColumn::DateTime(a), TypedColumn::DateTime(b)) => a.extend(b),
+            (TypedColumn::String, TypedColumn::String) => {}
+            _ => panic!("cannot extend a typed column with values of another type"),
+        }
+    }
+
+    /// Appends `count` missing values.
+    pub fn push_missing(&mut self, count: usize) {
+        match self {
+            TypedColumn::Integer(v) => v.extend(std::iter::repeat_n(None, count)),
+            TypedColumn::Float(v) => v.extend(std::iter::repeat_n(None, count)),
+            TypedColumn::Boolean(v) => v.extend(std::iter::repeat_n(None, count)),
+            TypedColumn::Date(v) => v.extend(std::iter::repeat_n(None, count)),
+            TypedColumn::DateTime(v) => v.extend(std::iter::repeat_n(None, count)),
+            TypedColumn::String => {}
+        }
+    }
+
+    /// Parses a new value into a row; empty and unparseable cells are missing.
+    pub fn set(&mut self, row: usize, cell: &str) {
+        let cell = cell.trim();
+        match self {
+            TypedColumn::Integer(v) => v[row] = parse_integer(cell),
+            TypedColumn::Float(v) => v[row] = parse_float(cell),
+            TypedColumn::Boolean(v) => v[row] = parse_boolean(cell),
+            TypedColumn::Date(v) => v[row] = parse_date(cell),
+            TypedColumn::DateTime(v) => v[row] = parse_datetime(cell),
+            TypedColumn::String => {}
+        }
+    }
+
+    /// Whether a row holds a parsed value. Text columns always do.
+    pub fn has_value(&self, row: usize) -> bool {
+        match self {
+            TypedColumn::Integer(v) => v[row].is_some(),
+            TypedColumn::Float(v) => v[row].is_some(),
+            TypedColumn::Boolean(v) => v[row].is_some(),
+            TypedColumn::Date(v) => v[row].is_some(),
+            TypedColumn::DateTime(v) => v[row].is_some(),
+            TypedColumn::String => true,
+        }
+    }
+
+    /// The parsed value of a row; text columns have none.
+    pub fn value(&self, row: usize) -> Option<TypedValue> {
+        match self {
+            TypedColumn::Integer(v) => v[row].map(|x| TypedValue::Number(x as f64)),
+            TypedColumn::Float(v) => v[row].map(TypedValue::Number),
+            TypedColumn::Boolean(v) => v[row].map(TypedValue::Boolean),
+            TypedColumn::Date(v) => v[row]
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(TypedValue::DateTime),
+            TypedColumn::DateTime(v) => v[row].map(TypedValue::DateTime),
+            TypedColumn::String => None,
+        }
+    }
+
+    /// Orders two rows by value. Missing values come first, as empty text would.
+    pub fn compare(&self, cells: &[String], a: usize, b: usize) -> Ordering {
+        match self {
+            TypedColumn::Integer(v) => v[a].cmp(&v[b]),
+            TypedColumn::Float(v) => match (v[a], v[b]) {
+                (Some(x), Some(y)) => x.total_cmp(&y),
+                (x, y) => x.is_some().cmp(&y.is_some()),
+            },
+            TypedColumn::Boolean(v) => v[a].cmp(&v[b]),
+            TypedColumn::Date(v) => v[a].cmp(&v[b]),
+            TypedColumn::DateTime(v) => v[a].cmp(&v[b]),
+            TypedColumn::String => cells[a].cmp(&cells[b]),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    fn infer(values: &[&str]) -> Option<ColumnType> {
+        infer_type(&cells(values), &[])
+    }
+
+    #[test]
+    fn detects_each_type() {
+        assert_eq!(infer(&["true", "FALSE"]), Some(ColumnType::Boolean));
+        assert_eq!(infer(&["1", "-20", " 3 "]), Some(ColumnType::Integer));
+        assert_eq!(infer(&["1", "2.5", "1e3"]), Some(ColumnType::Float));
+        assert_eq!(infer(&["2024-01-31", "1999-12-01"]), Some(ColumnType::Date));
+        assert_eq!(
+            infer(&["2024-01-31 10:00:00", "2024-02-01T08:30", "2024-03-01T00:00:00+02:00"]),
+            Some(ColumnType::DateTime)
+        );
+        assert_eq!(infer(&["1", "n/a"]), Some(ColumnType::String));
+        assert_eq!(infer(&["nan"]), Some(ColumnType::String));
+    }
+
+    #[test]
+    fn empty_and_null_cells_are_skipped() {
+        assert_eq!(infer(&["", "  "]), None);
+        assert_eq!(infer_type(&cells(&["x", "1"]), &[true, false]), Some(ColumnType::Integer));
+    }
+
+    #[test]
+    fn mixed_columns_widen() {
+        assert_eq!(infer(&["1", "2024-01-01"]), Some(ColumnType::String));
+        assert_eq!(infer(&["2024-01-01", "2024-01-02 10:00"]), Some(ColumnType::DateTime));
+    }
+
+    #[test]
+    fn join_picks_the_narrowest_common_type() {
+        use ColumnType::*;
+        assert_eq!(Integer.join(Integer), Integer);
+        assert_eq!(Integer.join(Float), Float);
+        assert_eq!(Float.join(Integer), Float);
+        assert_eq!(Date.join(DateTime), DateTime);
+        assert_eq!(Integer.join(Boolean), String);
+        assert_eq!(Date.join(Float), String);
+    }
+
+    #[test]
+    fn parse_counts_unreadable_cells() {
+        let (typed, failed) =
+            TypedColumn::parse(ColumnType::Integer, &cells(&["1", "x", "", "3"]), &[]);
+        assert_eq!(failed, 1);
+        assert_eq!(typed.value(0), Some(TypedValue::Number(1.0)));
+        assert!(!typed.has_value(1));
+        assert!(!typed.has_value(2));
+    }
+
+    #[test]
+    fn extend_appends_values_of_the_same_type() {
+        let (mut typed, _) = TypedColumn::parse(ColumnType::Float, &cells(&["1.5"]), &[]);
+        let (more, _) = TypedColumn::parse(ColumnType::Float, &cells(&["2"]), &[]);
+        typed.extend(more);
+        typed.push_missing(1);
+        assert_eq!(typed.value(1), Some(TypedValue::Number(2.0)));
+        assert!(!typed.has_value(2));
+    }
+}
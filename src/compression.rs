@@ -0,0 +1,173 @@
+// src/compression.rs
+
+use std::io::{self, Cursor, Read};
+
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
+
+/// Longest magic number we need to look at, the bzip2 header and block magic.
+const MAGIC_LEN: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "gz" | "gzip" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            "bz2" | "bzip2" => Some(Compression::Bzip2),
+            "xz" => Some(Compression::Xz),
+            _ => None,
+        }
+    }
+
+    /// Recognizes a stream by its first bytes. The gzip compression method
+    /// and the bzip2 block size and first block magic are checked too, so
+    /// that text starting with `BZh` is not taken for bzip2.
+    pub fn from_magic(bytes: &[u8]) -> Option<Self> {
+        const BZIP2_BLOCK: [u8; 6] = [0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+        const BZIP2_END: [u8; 6] = [0x17, 0x72, 0x45, 0x38, 0x50, 0x90];
+        if bytes.starts_with(&[0x1f, 0x8b, 0x08]) {
+            Some(Compression::Gzip)
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else if bytes.len() == MAGIC_LEN
+            && bytes.starts_with(b"BZh")
+            && (b'1'..=b'9').contains(&bytes[3])
+            && (bytes[4..] == BZIP2_BLOCK || bytes[4..] == BZIP2_END)
+        {
+            Some(Compression::Bzip2)
+        } else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Compression::Xz)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Bzip2 => "bzip2",
+            Compression::Xz => "xz",
+        }
+    }
+
+    /// Wraps a compressed stream in a decoder that inflates it as it is read.
+    pub fn decoder(self, reader: Box<dyn Read>) -> io::Result<Box<dyn Read>> {
+        Ok(match self {
+            Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+            Compression::Bzip2 => Box::new(MultiBzDecoder::new(reader)),
+            Compression::Xz => Box::new(XzDecoder::new_multi_decoder(reader)),
+        })
+    }
+}
+
+/// Splits a compression suffix off a path, so `sales.csv.gz` yields
+/// `sales.csv` and gzip. Paths without a known suffix are returned as is.
+pub fn strip_compression_suffix(path: &str) -> (&str, Option<Compression>) {
+    if let Some((stem, extension)) = path.rsplit_once('.') {
+        if let Some(compression) = Compression::from_extension(extension) {
+            return (stem, Some(compression));
+        }
+    }
+    (path, None)
+}
+
+/// Checks the first bytes of a stream read from `path` for a compression
+/// magic number and returns a reader yielding the decompressed data, along
+/// with the format that was found. Uncompressed streams are passed through
+/// untouched.
+///
+/// A compression suffix on the path has to agree with the magic number, so
+/// that a mislabelled file fails instead of being parsed as compressed bytes.
+pub fn decompress(
+    mut reader: Box<dyn Read>,
+    path: &str,
+) -> io::Result<(Box<dyn Read>, Option<Compression>)> {
+    let mut magic = Vec::with_capacity(MAGIC_LEN);
+    (&mut reader).take(MAGIC_LEN as u64).read_to_end(&mut magic)?;
+
+    let compression = Compression::from_magic(&magic);
+    if let (_, Some(expected)) = strip_compression_suffix(path) {
+        if compression != Some(expected) && !magic.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not {} compressed", path, expected.name()),
+            ));
+        }
+    }
+
+    let replayed: Box<dyn Read> = Box::new(Cursor::new(magic).chain(reader));
+    match compression {
+        Some(compression) => Ok((compression.decoder(replayed)?, Some(compression))),
+        None => Ok((replayed, None)),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn read(bytes: Vec<u8>, path: &str) -> io::Result<(String, Option<Compression>)> {
+        let (mut reader, compression) = decompress(Box::new(Cursor::new(bytes)), path)?;
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        Ok((text, compression))
+    }
+
+    fn gzip(text: &str) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn bzip2(text: &str) -> Vec<u8> {
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn magic_bytes_are_found_without_a_suffix() {
+        let (text, compression) = read(gzip("a,b\n1,2\n"), "data.csv").unwrap();
+        assert_eq!(text, "a,b\n1,2\n");
+        assert_eq!(compression, Some(Compression::Gzip));
+
+        let (text, compression) = read(bzip2("a\n"), "-").unwrap();
+        assert_eq!(text, "a\n");
+        assert_eq!(compression, Some(Compression::Bzip2));
+    }
+
+    #[test]
+    fn text_starting_with_bzh_is_plain() {
+        let (text, compression) = read(b"BZh,x\n1,2\n".to_vec(), "data.csv").unwrap();
+        assert_eq!(text, "BZh,x\n1,2\n");
+        assert_eq!(compression, None);
+    }
+
+    #[test]
+    fn suffix_and_magic_must_agree() {
+        assert!(read(b"a,b\n1,2\n".to_vec(), "data.csv.gz").is_err());
+        assert!(read(gzip("a\n"), "data.csv.zst").is_err());
+        let (text, _) = read(gzip("a\n"), "data.csv.gz").unwrap();
+        assert_eq!(text, "a\n");
+    }
+
+    #[test]
+    fn compression_suffixes_are_split_off() {
+        let gzipped = ("sales.csv", Some(Compression::Gzip));
+        assert_eq!(strip_compression_suffix("sales.csv.GZ"), gzipped);
+        assert_eq!(strip_compression_suffix("sales.csv"), ("sales.csv", None));
+    }
+}
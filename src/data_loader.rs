@@ -1,55 +1,720 @@
 // src/data_loader.rs
 
 use std::error::Error;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Write};
 
+use tempfile::NamedTempFile;
 
-#[derive(Debug)]
+use crate::column_type::{infer_type, ColumnType, TypedColumn};
+use crate::json_loader::{JsonLoader, JsonlLoader};
+use crate::parquet_loader::ParquetLoader;
+use crate::spreadsheet_loader::SpreadsheetLoader;
+
+
+#[derive(Clone, Debug)]
 pub struct TableData {
     pub headers: Vec<String>,
     pub columns: Vec<Vec<String>>,
+    /// Per-column null flags. An empty vector means the column has no nulls,
+    /// which is always the case for formats without a notion of null.
+    pub nulls: Vec<Vec<bool>>,
+    /// Per-column types; `None` until a column has had a value to infer from.
+    pub types: Vec<Option<ColumnType>>,
+    /// The cells of each column parsed as its type, used for sorting and aggregation.
+    pub typed: Vec<TypedColumn>,
+    /// Columns whose type was set by hand; rows streamed in later are parsed
+    /// as that type instead of being inferred.
+    pub manual_types: Vec<bool>,
 }
 
 impl TableData {
     pub fn new(headers: Vec<String>, columns: Vec<Vec<String>>) -> Self {
-        TableData { headers, columns }
+        let nulls = vec![Vec::new(); columns.len()];
+        Self::typed(headers, columns, nulls)
+    }
+
+    pub fn with_nulls(
+        headers: Vec<String>,
+        columns: Vec<Vec<String>>,
+        mut nulls: Vec<Vec<bool>>,
+    ) -> Self {
+        for flags in &mut nulls {
+            if !flags.contains(&true) {
+                flags.clear();
+            }
+        }
+        nulls.resize(columns.len(), Vec::new());
+        Self::typed(headers, columns, nulls)
+    }
+
+    fn typed(headers: Vec<String>, columns: Vec<Vec<String>>, nulls: Vec<Vec<bool>>) -> Self {
+        let types: Vec<Option<ColumnType>> = columns
+            .iter()
+            .zip(&nulls)
+            .map(|(cells, flags)| infer_type(cells, flags))
+            .collect();
+        let typed = columns
+            .iter()
+            .zip(&nulls)
+            .zip(&types)
+            .map(|((cells, flags), ty)| {
+                TypedColumn::parse(ty.unwrap_or(ColumnType::String), cells, flags).0
+            })
+            .collect();
+        let manual_types = vec![false; columns.len()];
+        TableData { headers, columns, nulls, types, typed, manual_types }
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.columns.first().map_or(0, Vec::len)
+    }
+
+    /// Columns with nothing to infer from are shown as text.
+    pub fn column_type(&self, col: usize) -> ColumnType {
+        self.types[col].unwrap_or(ColumnType::String)
+    }
+
+    /// Reparses a column as the given type and returns how many of its
+    /// cells could not be read that way.
+    pub fn set_column_type(&mut self, col: usize, column_type: ColumnType) -> usize {
+        self.manual_types[col] = true;
+        self.reparse_column(col, column_type)
+    }
+
+    fn reparse_column(&mut self, col: usize, column_type: ColumnType) -> usize {
+        let (typed, failed) = TypedColumn::parse(column_type, &self.columns[col], &self.nulls[col]);
+        self.types[col] = Some(column_type);
+        self.typed[col] = typed;
+        failed
+    }
+
+    /// Drops a manual type and goes back to the inferred one.
+    pub fn infer_column_type(&mut self, col: usize) {
+        let inferred = infer_type(&self.columns[col], &self.nulls[col]);
+        let column_type = inferred.unwrap_or(ColumnType::String);
+        self.typed[col] = TypedColumn::parse(column_type, &self.columns[col], &self.nulls[col]).0;
+        self.types[col] = inferred;
+        self.manual_types[col] = false;
+    }
+
+    /// Appends the rows of a chunk produced by a streaming loader. The chunk's
+    /// headers extend ours when new columns turned up part way through.
+    pub fn append(&mut self, mut chunk: TableData) {
+        let existing_rows = self.num_rows();
+        let chunk_rows = chunk.num_rows();
+
+        while self.headers.len() < chunk.headers.len() {
+            self.headers.push(chunk.headers[self.headers.len()].clone());
+            self.columns.push(vec![String::new(); existing_rows]);
+            self.nulls.push(Vec::new());
+            self.types.push(None);
+            self.typed.push(TypedColumn::String);
+            self.manual_types.push(false);
+        }
+
+        for col in 0..self.columns.len() {
+            match chunk.columns.get_mut(col) {
+                Some(cells) => self.columns[col].append(cells),
+                None => self.columns[col].resize(existing_rows + chunk_rows, String::new()),
+            }
+
+            let chunk_nulls = chunk.nulls.get_mut(col).map(std::mem::take).unwrap_or_default();
+            if !chunk_nulls.is_empty() {
+                self.nulls[col].resize(existing_rows, false);
+                self.nulls[col].extend(chunk_nulls);
+            } else if !self.nulls[col].is_empty() {
+                self.nulls[col].resize(existing_rows + chunk_rows, false);
+            }
+
+            // A column typed by hand keeps its type and has the new cells parsed as it.
+            if self.manual_types[col] {
+                let cells = &self.columns[col][existing_rows..];
+                let nulls = self.nulls[col].get(existing_rows..).unwrap_or(&[]);
+                let (chunk_typed, _) = TypedColumn::parse(self.column_type(col), cells, nulls);
+                self.typed[col].extend(chunk_typed);
+                continue;
+            }
+
+            // Values of the same type are carried over as parsed; a chunk that
+            // needs a wider type has the whole column parsed again.
+            let chunk_type = chunk.types.get(col).copied().flatten();
+            match (self.types[col], chunk_type) {
+                (_, None) => self.typed[col].push_missing(chunk_rows),
+                (Some(ty), Some(chunk_ty)) if ty == chunk_ty => {
+                    let chunk_typed =
+                        std::mem::replace(&mut chunk.typed[col], TypedColumn::String);
+                    self.typed[col].extend(chunk_typed);
+                }
+                (ty, Some(chunk_ty)) => {
+                    let joined = ty.map_or(chunk_ty, |ty| ty.join(chunk_ty));
+                    self.reparse_column(col, joined);
+                }
+            }
+        }
+    }
+
+    /// Replaces a cell, `None` making it null, and parses it as the column's
+    /// type. An inferred type is widened to fit a value it cannot hold, as for
+    /// appended chunks; a type set by hand is kept.
+    pub fn set_cell(&mut self, col: usize, row: usize, value: Option<String>) {
+        let is_null = value.is_none();
+        if is_null || !self.nulls[col].is_empty() {
+            let rows = self.num_rows();
+            self.nulls[col].resize(rows, false);
+            self.nulls[col][row] = is_null;
+        }
+        self.columns[col][row] = value.unwrap_or_default();
+        let cell = if is_null { "" } else { &self.columns[col][row] };
+        self.typed[col].set(row, cell);
+
+        let fits = self.types[col].is_some() && self.typed[col].has_value(row);
+        if is_null || fits || self.manual_types[col] {
+            return;
+        }
+        if let Some(cell_type) = infer_type(std::slice::from_ref(&self.columns[col][row]), &[]) {
+            let joined = self.types[col].map_or(cell_type, |ty| ty.join(cell_type));
+            self.reparse_column(col, joined);
+        }
+    }
+
+    /// Adds a row of empty cells at the end and returns its index.
+    pub fn push_empty_row(&mut self) -> usize {
+        let row = self.num_rows();
+        for col in 0..self.columns.len() {
+            self.columns[col].push(String::new());
+            if !self.nulls[col].is_empty() {
+                self.nulls[col].push(false);
+            }
+            self.typed[col].push_missing(1);
+        }
+        row
+    }
+
+    pub fn is_null(&self, col: usize, row: usize) -> bool {
+        self.nulls
+            .get(col)
+            .and_then(|flags| flags.get(row))
+            .copied()
+            .unwrap_or(false)
     }
 }
 
 
-pub trait DataLoader {
-    fn load(&self, path: &str) -> Result<TableData, Box<dyn Error>>;
+pub trait DataLoader: Send {
+    fn load(&self, path: &str) -> Result<TableData, Box<dyn Error>> {
+        self.load_reader(Box::new(File::open(path)?))
+    }
+
+    /// Loads from a byte stream that may not be seekable, such as standard input.
+    fn load_reader(&self, reader: Box<dyn Read>) -> Result<TableData, Box<dyn Error>>;
+
+    /// Loads from a byte stream, handing rows over in chunks as they are parsed.
+    /// Loading stops early when `on_chunk` returns false.
+    ///
+    /// Formats that cannot be read incrementally deliver everything in one chunk.
+    fn stream_reader(
+        &self,
+        reader: Box<dyn Read>,
+        on_chunk: &mut dyn FnMut(TableData) -> bool,
+    ) -> Result<(), Box<dyn Error>> {
+        on_chunk(self.load_reader(reader)?);
+        Ok(())
+    }
 }
 
+/// Rows per chunk for loaders that stream.
+pub const CHUNK_ROWS: usize = 10_000;
 
-pub struct CsvLoader;
+/// Runs a streaming load to completion and gathers its chunks into one table.
+pub fn collect_chunks(
+    stream: impl FnOnce(&mut dyn FnMut(TableData) -> bool) -> Result<(), Box<dyn Error>>,
+) -> Result<TableData, Box<dyn Error>> {
+    let mut table = TableData::new(Vec::new(), Vec::new());
+    stream(&mut |chunk| {
+        table.append(chunk);
+        true
+    })?;
+    Ok(table)
+}
 
-impl DataLoader for CsvLoader {
-    fn load(&self, path: &str) -> Result<TableData, Box<dyn Error>> {
-        let mut reader = csv::Reader::from_path(path)?;
-        let headers = reader
-            .headers()?
+
+/// Path argument that stands for standard input.
+pub const STDIN_PATH: &str = "-";
+
+/// How many bytes from the start of the file the sniffer looks at.
+const SNIFF_BYTES: usize = 8 * 1024;
+
+/// Delimiters the sniffer will consider, in order of preference on ties.
+const CANDIDATE_DELIMITERS: [u8; 5] = [b',', b'\t', b';', b'|', b':'];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub has_headers: bool,
+    /// Every field is quoted, rather than only those that need it.
+    pub always_quoted: bool,
+    /// Lines end with `\r\n` rather than `\n`.
+    pub crlf: bool,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect {
+            delimiter: b',',
+            quote: b'"',
+            has_headers: true,
+            always_quoted: false,
+            crlf: false,
+        }
+    }
+}
+
+/// Guesses the dialect of a delimited text sample.
+///
+/// The delimiter is the candidate that appears the same non-zero number of
+/// times on the most lines, the quote is whichever of `"` and `'` is seen
+/// wrapping fields, and the first row is treated as a header when it does
+/// not look like the rows below it. Quoting and line endings are noted so
+/// that the file can be written back the same way.
+pub fn sniff_dialect(sample: &[u8], delimiter: Option<u8>) -> Dialect {
+    let text = String::from_utf8_lossy(sample);
+    let mut lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+
+    // The sample is cut at an arbitrary byte, so the last line may be partial.
+    if lines.len() > 1 && !text.ends_with('\n') {
+        lines.pop();
+    }
+
+    let crlf = text.contains("\r\n");
+    if lines.is_empty() {
+        return Dialect {
+            delimiter: delimiter.unwrap_or(b','),
+            crlf,
+            ..Dialect::default()
+        };
+    }
+
+    let quote = sniff_quote(&lines);
+    let delimiter = delimiter.unwrap_or_else(|| sniff_delimiter(&lines, quote));
+    let rows: Vec<Vec<String>> = lines
+        .iter()
+        .map(|line| split_line(line, delimiter, quote))
+        .collect();
+
+    Dialect {
+        delimiter,
+        quote,
+        has_headers: sniff_has_headers(&rows),
+        always_quoted: sniff_always_quoted(&lines, delimiter, quote),
+        crlf,
+    }
+}
+
+fn sniff_quote(lines: &[&str]) -> u8 {
+    let mut double = 0;
+    let mut single = 0;
+    for line in lines {
+        let bytes = line.as_bytes();
+        for (i, &b) in bytes.iter().enumerate() {
+            let at_field_start = i == 0 || CANDIDATE_DELIMITERS.contains(&bytes[i - 1]);
+            if !at_field_start {
+                continue;
+            }
+            match b {
+                b'"' => double += 1,
+                b'\'' => single += 1,
+                _ => {}
+            }
+        }
+    }
+    if single > double {
+        b'\''
+    } else {
+        b'"'
+    }
+}
+
+fn sniff_always_quoted(lines: &[&str], delimiter: u8, quote: u8) -> bool {
+    lines.iter().all(|line| {
+        let mut in_quotes = false;
+        let mut at_field_start = true;
+        for &b in line.trim_end_matches('\r').as_bytes() {
+            if at_field_start && b != quote {
+                return false;
+            }
+            at_field_start = false;
+            if b == quote {
+                in_quotes = !in_quotes;
+            } else if b == delimiter && !in_quotes {
+                at_field_start = true;
+            }
+        }
+        // A line ending in a delimiter has an empty, unquoted last field.
+        !at_field_start
+    })
+}
+
+fn sniff_delimiter(lines: &[&str], quote: u8) -> u8 {
+    let mut best = (b',', 0usize, 0usize);
+
+    for &candidate in &CANDIDATE_DELIMITERS {
+        let counts: Vec<usize> = lines
             .iter()
-            .map(String::from)
-            .collect::<Vec<String>>();
+            .map(|line| count_unquoted(line, candidate, quote))
+            .collect();
 
-        let mut columns: Vec<Vec<String>> = headers.iter().map(|_| Vec::new()).collect();
+        // Find the most common per-line count, ignoring lines without the delimiter.
+        let mut frequencies: Vec<(usize, usize)> = Vec::new();
+        for &count in counts.iter().filter(|&&c| c > 0) {
+            match frequencies.iter_mut().find(|(c, _)| *c == count) {
+                Some((_, n)) => *n += 1,
+                None => frequencies.push((count, 1)),
+            }
+        }
+        let Some(&(mode, lines_with_mode)) = frequencies.iter().max_by_key(|(c, n)| (*n, *c)) else {
+            continue;
+        };
+
+        if (lines_with_mode, mode) > (best.1, best.2) {
+            best = (candidate, lines_with_mode, mode);
+        }
+    }
+
+    best.0
+}
 
-        for result in reader.records() {
+fn count_unquoted(line: &str, delimiter: u8, quote: u8) -> usize {
+    let mut in_quotes = false;
+    let mut count = 0;
+    for &b in line.as_bytes() {
+        if b == quote {
+            in_quotes = !in_quotes;
+        } else if b == delimiter && !in_quotes {
+            count += 1;
+        }
+    }
+    count
+}
+
+fn split_line(line: &str, delimiter: u8, quote: u8) -> Vec<String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .quote(quote)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(line.as_bytes());
+    reader
+        .records()
+        .next()
+        .and_then(|r| r.ok())
+        .map(|record| record.iter().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+fn sniff_has_headers(rows: &[Vec<String>]) -> bool {
+    if rows.len() < 2 {
+        return true;
+    }
+
+    let header = &rows[0];
+    let body = &rows[1..];
+    let mut votes = 0i32;
+
+    for (col, name) in header.iter().enumerate() {
+        let values: Vec<&str> = body
+            .iter()
+            .filter_map(|row| row.get(col).map(String::as_str))
+            .filter(|v| !v.is_empty())
+            .collect();
+        if values.is_empty() {
+            continue;
+        }
+
+        if values.iter().all(|v| v.parse::<f64>().is_ok()) {
+            // A numeric column whose first cell is not a number is a good header sign.
+            votes += if name.parse::<f64>().is_ok() { -1 } else { 1 };
+        } else {
+            let len = values[0].len();
+            if values.iter().all(|v| v.len() == len) {
+                votes += if name.len() == len { -1 } else { 1 };
+            }
+        }
+    }
+
+    votes >= 0
+}
+
+
+/// Loads comma, tab, semicolon, pipe or any other single-byte delimited text.
+///
+/// Anything not set explicitly is guessed from the start of the file.
+#[derive(Default)]
+pub struct DelimitedLoader {
+    pub delimiter: Option<u8>,
+}
+
+impl DelimitedLoader {
+    pub fn new() -> Self {
+        DelimitedLoader { delimiter: None }
+    }
+
+    pub fn with_delimiter(delimiter: u8) -> Self {
+        DelimitedLoader {
+            delimiter: Some(delimiter),
+        }
+    }
+
+    /// The dialect loading from `reader` would find.
+    pub fn sniff(&self, reader: impl Read) -> io::Result<Dialect> {
+        let mut sample = Vec::with_capacity(SNIFF_BYTES);
+        reader.take(SNIFF_BYTES as u64).read_to_end(&mut sample)?;
+        Ok(sniff_dialect(&sample, self.delimiter))
+    }
+}
+
+impl DataLoader for DelimitedLoader {
+    fn load_reader(&self, reader: Box<dyn Read>) -> Result<TableData, Box<dyn Error>> {
+        collect_chunks(|on_chunk| self.stream_reader(reader, on_chunk))
+    }
+
+    fn stream_reader(
+        &self,
+        mut reader: Box<dyn Read>,
+        on_chunk: &mut dyn FnMut(TableData) -> bool,
+    ) -> Result<(), Box<dyn Error>> {
+        // The sniffed prefix is replayed in front of the rest of the stream.
+        let mut sample = Vec::with_capacity(SNIFF_BYTES);
+        (&mut reader)
+            .take(SNIFF_BYTES as u64)
+            .read_to_end(&mut sample)?;
+        let dialect = sniff_dialect(&sample, self.delimiter);
+
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(dialect.delimiter)
+            .quote(dialect.quote)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(Cursor::new(sample).chain(reader));
+
+        let mut records = reader.records();
+        let mut headers = Vec::new();
+        let mut columns: Vec<Vec<String>> = Vec::new();
+
+        if dialect.has_headers {
+            if let Some(record) = records.next() {
+                headers = record?.iter().map(String::from).collect();
+                columns = headers.iter().map(|_| Vec::new()).collect();
+            }
+        }
+
+        for result in records {
             let record = result?;
-            for (i, field) in record.iter().enumerate() {
-                columns[i].push(field.to_string());
+
+            // Rows wider than the header get extra, generically named columns.
+            while columns.len() < record.len() {
+                let filled = columns.first().map_or(0, Vec::len);
+                columns.push(vec![String::new(); filled]);
+                headers.push(format!("column_{}", headers.len() + 1));
+            }
+
+            for (i, column) in columns.iter_mut().enumerate() {
+                column.push(record.get(i).unwrap_or("").to_string());
+            }
+
+            if columns.first().is_some_and(|c| c.len() >= CHUNK_ROWS) {
+                let chunk = columns.iter_mut().map(std::mem::take).collect();
+                if !on_chunk(TableData::new(headers.clone(), chunk)) {
+                    return Ok(());
+                }
             }
         }
 
-        Ok(TableData::new(headers, columns))
+        on_chunk(TableData::new(headers, columns));
+        Ok(())
+    }
+}
+
+
+/// Opens a path for reading, with `-` standing for standard input.
+pub fn open_input(path: &str) -> io::Result<Box<dyn Read + Send>> {
+    if path == STDIN_PATH {
+        Ok(Box::new(io::stdin()))
+    } else {
+        Ok(Box::new(File::open(path)?))
+    }
+}
+
+
+/// Copies a stream into a temporary file for consumers that need a real,
+/// seekable path. The file is removed when the returned handle is dropped.
+pub fn spool_to_temp_file(
+    mut reader: Box<dyn Read>,
+    extension: &str,
+) -> Result<NamedTempFile, Box<dyn Error>> {
+    let mut file = tempfile::Builder::new()
+        .prefix("fastdata-")
+        .suffix(&format!(".{}", extension))
+        .tempfile()?;
+    io::copy(&mut reader, &mut file)?;
+    file.flush()?;
+    Ok(file)
+}
+
+
+/// Parses a delimiter given on the command line: a single byte or a name.
+pub fn parse_delimiter(spec: &str) -> Result<u8, Box<dyn Error>> {
+    match spec {
+        "\\t" | "tab" => Ok(b'\t'),
+        "space" => Ok(b' '),
+        _ if spec.len() == 1 => Ok(spec.as_bytes()[0]),
+        _ => Err(format!("Delimiter '{}' must be a single byte character", spec).into()),
     }
 }
 
 
+/// The loader for a delimited text extension, such as `csv` or `tsv`.
+pub fn delimited_loader(extension: &str) -> Option<DelimitedLoader> {
+    match extension.to_lowercase().as_str() {
+        "csv" => Some(DelimitedLoader::new()),
+        "tsv" | "tab" => Some(DelimitedLoader::with_delimiter(b'\t')),
+        "psv" => Some(DelimitedLoader::with_delimiter(b'|')),
+        _ => None,
+    }
+}
+
 pub fn get_loader(extension: &str) -> Result<Box<dyn DataLoader>, Box<dyn Error>> {
+    if let Some(loader) = delimited_loader(extension) {
+        return Ok(Box::new(loader));
+    }
     match extension.to_lowercase().as_str() {
-        "csv" => Ok(Box::new(CsvLoader)),
+        "json" => Ok(Box::new(JsonLoader)),
+        "jsonl" | "ndjson" => Ok(Box::new(JsonlLoader)),
+        "parquet" | "pq" => Ok(Box::new(ParquetLoader)),
+        "xlsx" | "xlsm" | "xlsb" | "xls" | "ods" => Ok(Box::new(SpreadsheetLoader)),
 
         _ => Err(format!("File format '{}' is not supported", extension).into()),
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::column_type::TypedValue;
+
+    fn table(headers: &[&str], rows: &[&[&str]]) -> TableData {
+        let columns = (0..headers.len())
+            .map(|col| rows.iter().map(|row| row[col].to_string()).collect())
+            .collect();
+        TableData::new(headers.iter().map(|h| h.to_string()).collect(), columns)
+    }
+
+    #[test]
+    fn chunks_of_another_type_widen_the_column() {
+        let mut data = table(&["n", "when"], &[&["1", "2024-01-01"], &["2", "2024-01-02"]]);
+        data.append(table(&["n", "when"], &[&["2.5", "2024-01-03 10:00"]]));
+        assert_eq!(data.types, [Some(ColumnType::Float), Some(ColumnType::DateTime)]);
+        data.append(table(&["n", "when"], &[&["3", "soon"]]));
+        assert_eq!(data.types, [Some(ColumnType::Float), Some(ColumnType::String)]);
+        assert_eq!(data.num_rows(), 4);
+        assert_eq!(data.typed[0].value(3), Some(TypedValue::Number(3.0)));
+    }
+
+    #[test]
+    fn chunks_can_add_columns() {
+        let mut data = table(&["a"], &[&["1"]]);
+        data.append(table(&["a", "b"], &[&["2", "x"]]));
+        assert_eq!(data.headers, ["a", "b"]);
+        assert_eq!(data.columns[1], ["", "x"]);
+        assert_eq!(data.types[1], Some(ColumnType::String));
+    }
+
+    #[test]
+    fn hand_set_types_survive_later_chunks() {
+        let mut data = table(&["code"], &[&["007"]]);
+        data.set_column_type(0, ColumnType::String);
+        data.append(table(&["code"], &[&["8"]]));
+        assert_eq!(data.types[0], Some(ColumnType::String));
+    }
+
+    #[test]
+    fn edited_cells_widen_inferred_types() {
+        let mut data = table(&["n", "code", "empty"], &[&["1", "1", ""], &["2", "2", ""]]);
+        data.set_cell(0, 1, Some("2.5".to_string()));
+        assert_eq!(data.types[0], Some(ColumnType::Float));
+        assert_eq!(data.typed[0].value(0), Some(TypedValue::Number(1.0)));
+        assert_eq!(data.typed[0].value(1), Some(TypedValue::Number(2.5)));
+
+        data.set_cell(1, 0, Some("n/a".to_string()));
+        assert_eq!(data.types[1], Some(ColumnType::String));
+        data.set_cell(2, 0, Some("7".to_string()));
+        assert_eq!(data.types[2], Some(ColumnType::Integer));
+        assert_eq!(data.typed[2].value(0), Some(TypedValue::Number(7.0)));
+
+        data.set_cell(0, 0, None);
+        data.set_cell(0, 1, Some(String::new()));
+        assert_eq!(data.types[0], Some(ColumnType::Float));
+    }
+
+    #[test]
+    fn edited_cells_keep_hand_set_types() {
+        let mut data = table(&["n"], &[&["1"], &["2"]]);
+        data.set_column_type(0, ColumnType::Integer);
+        data.set_cell(0, 1, Some("two".to_string()));
+        assert_eq!(data.types[0], Some(ColumnType::Integer));
+        assert!(!data.typed[0].has_value(1));
+    }
+
+    #[test]
+    fn semicolons_win_over_decimal_commas() {
+        let dialect = sniff_dialect(b"name;price;qty\nbolt;1,5;10\nnut;0,25;200\n", None);
+        assert_eq!(dialect.delimiter, b';');
+        assert!(dialect.has_headers);
+    }
+
+    #[test]
+    fn delimiters_inside_quotes_are_not_counted() {
+        let sample = b"name,city\n\"Smith; John\",Paris\n\"Doe; Jane\",Rome\n";
+        let dialect = sniff_dialect(sample, None);
+        assert_eq!(dialect.delimiter, b',');
+        assert_eq!(dialect.quote, b'"');
+    }
+
+    #[test]
+    fn single_quotes_are_picked_up() {
+        let dialect = sniff_dialect(b"'a|b'|'c'\n'd'|'e|f'\n", None);
+        assert_eq!(dialect.quote, b'\'');
+        assert_eq!(dialect.delimiter, b'|');
+        assert!(dialect.always_quoted);
+    }
+
+    #[test]
+    fn numeric_first_row_is_data() {
+        let dialect = sniff_dialect(b"1,2.5,3\n4,5.5,6\n7,8.5,9\n", None);
+        assert_eq!(dialect.delimiter, b',');
+        assert!(!dialect.has_headers);
+    }
+
+    #[test]
+    fn single_column_keeps_its_header() {
+        let dialect = sniff_dialect(b"amount\n10\n20\n", None);
+        assert_eq!(dialect.delimiter, b',');
+        assert!(dialect.has_headers);
+    }
+
+    #[test]
+    fn explicit_delimiter_and_line_endings() {
+        let dialect = sniff_dialect(b"a,b;c\r\n1,2;3\r\n", Some(b';'));
+        assert_eq!(dialect.delimiter, b';');
+        assert!(dialect.crlf);
+        assert!(!dialect.always_quoted);
+    }
+
+    #[test]
+    fn partial_last_line_is_ignored() {
+        let dialect = sniff_dialect(b"a\tb\n1\t2\n3\t4\n5,6,7,8,9", None);
+        assert_eq!(dialect.delimiter, b'\t');
+    }
+}
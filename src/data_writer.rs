@@ -0,0 +1,336 @@
+// src/data_writer.rs
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use serde_json::{Map, Number, Value};
+
+use crate::column_type::TypedColumn;
+use crate::data_loader::Dialect;
+use crate::virtual_table::VirtualTable;
+
+
+/// Writes a view of a table: its shown rows and columns, in view order.
+pub trait DataWriter {
+    fn save(&self, table: &VirtualTable, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(table, &mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn write(&self, table: &VirtualTable, writer: &mut dyn Write) -> Result<(), Box<dyn Error>>;
+}
+
+
+/// Writes delimited text, with a header row unless the dialect has none.
+/// Nulls become empty fields.
+pub struct DelimitedWriter {
+    pub dialect: Dialect,
+}
+
+impl DelimitedWriter {
+    pub fn new() -> Self {
+        Self::with_dialect(Dialect::default())
+    }
+
+    pub fn with_delimiter(delimiter: u8) -> Self {
+        Self::with_dialect(Dialect {
+            delimiter,
+            ..Dialect::default()
+        })
+    }
+
+    /// Writes the way a sniffed file was written, to save changes back to it.
+    pub fn with_dialect(dialect: Dialect) -> Self {
+        DelimitedWriter { dialect }
+    }
+}
+
+impl Default for DelimitedWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DataWriter for DelimitedWriter {
+    fn write(&self, table: &VirtualTable, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        let dialect = &self.dialect;
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(dialect.delimiter)
+            .quote(dialect.quote)
+            .quote_style(if dialect.always_quoted {
+                csv::QuoteStyle::Always
+            } else {
+                csv::QuoteStyle::Necessary
+            })
+            .terminator(if dialect.crlf {
+                csv::Terminator::CRLF
+            } else {
+                csv::Terminator::Any(b'\n')
+            })
+            .from_writer(writer);
+        if dialect.has_headers {
+            writer.write_record((0..table.num_columns()).map(|col| table.header(col)))?;
+        }
+        for row in 0..table.num_rows() {
+            writer.write_record((0..table.num_columns()).map(|col| text(table, col, row)))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+
+/// Writes a JSON array with an object per row.
+pub struct JsonWriter;
+
+impl DataWriter for JsonWriter {
+    fn write(&self, table: &VirtualTable, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        writer.write_all(b"[")?;
+        for row in 0..table.num_rows() {
+            writer.write_all(if row == 0 { b"\n  " } else { b",\n  " })?;
+            serde_json::to_writer(&mut *writer, &record(table, row))?;
+        }
+        writer.write_all(b"\n]\n")?;
+        Ok(())
+    }
+}
+
+
+/// Writes JSON Lines: an object per row, one per line.
+pub struct JsonlWriter;
+
+impl DataWriter for JsonlWriter {
+    fn write(&self, table: &VirtualTable, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        for row in 0..table.num_rows() {
+            serde_json::to_writer(&mut *writer, &record(table, row))?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Writes a GitHub-flavored Markdown table, with numeric columns right-aligned.
+pub struct MarkdownWriter;
+
+impl DataWriter for MarkdownWriter {
+    fn write(&self, table: &VirtualTable, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        let columns = 0..table.num_columns();
+        let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+
+        let headers = columns.clone().map(|col| escape_markdown(table.header(col)));
+        writer.write_all(line(headers.collect()).as_bytes())?;
+        let rules = columns.clone().map(|col| {
+            if table.column_type(col).is_numeric() {
+                "---:".to_string()
+            } else {
+                "---".to_string()
+            }
+        });
+        writer.write_all(line(rules.collect()).as_bytes())?;
+
+        for row in 0..table.num_rows() {
+            let cells = columns.clone().map(|col| escape_markdown(text(table, col, row)));
+            writer.write_all(line(cells.collect()).as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Writes a standalone HTML page holding the table.
+pub struct HtmlWriter;
+
+impl DataWriter for HtmlWriter {
+    fn write(&self, table: &VirtualTable, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        let columns = 0..table.num_columns();
+        let align = |col: usize| {
+            if table.column_type(col).is_numeric() {
+                " style=\"text-align: right\""
+            } else {
+                ""
+            }
+        };
+
+        writeln!(writer, "<!DOCTYPE html>")?;
+        writeln!(writer, "<html>\n<head>\n<meta charset=\"utf-8\">\n</head>\n<body>")?;
+        writeln!(writer, "<table>\n<thead>\n<tr>")?;
+        for col in columns.clone() {
+            writeln!(writer, "<th{}>{}</th>", align(col), escape_html(table.header(col)))?;
+        }
+        writeln!(writer, "</tr>\n</thead>\n<tbody>")?;
+        for row in 0..table.num_rows() {
+            write!(writer, "<tr>")?;
+            for col in columns.clone() {
+                write!(writer, "<td{}>{}</td>", align(col), escape_html(text(table, col, row)))?;
+            }
+            writeln!(writer, "</tr>")?;
+        }
+        writeln!(writer, "</tbody>\n</table>\n</body>\n</html>")?;
+        Ok(())
+    }
+}
+
+
+pub fn get_writer(extension: &str) -> Result<Box<dyn DataWriter>, Box<dyn Error>> {
+    match extension.to_lowercase().as_str() {
+        "csv" => Ok(Box::new(DelimitedWriter::new())),
+        "tsv" | "tab" => Ok(Box::new(DelimitedWriter::with_delimiter(b'\t'))),
+        "psv" => Ok(Box::new(DelimitedWriter::with_delimiter(b'|'))),
+        "json" => Ok(Box::new(JsonWriter)),
+        "jsonl" | "ndjson" => Ok(Box::new(JsonlWriter)),
+        "md" | "markdown" => Ok(Box::new(MarkdownWriter)),
+        "html" | "htm" => Ok(Box::new(HtmlWriter)),
+
+        _ => Err(format!("Cannot save as '{}'", extension).into()),
+    }
+}
+
+/// Picks the writer from the extension of `path`.
+pub fn writer_for_path(path: &str) -> Result<Box<dyn DataWriter>, Box<dyn Error>> {
+    let extension = std::path::Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .ok_or("Add an extension to choose the format, such as .csv or .json")?;
+    get_writer(extension)
+}
+
+
+/// The text of a cell, empty for nulls.
+fn text(table: &VirtualTable, col: usize, row: usize) -> &str {
+    if table.is_null(col, row) {
+        ""
+    } else {
+        table.cell(col, row)
+    }
+}
+
+/// A row as a JSON object. Numbers and booleans keep their type; dates and
+/// text are strings, and nulls or empty typed cells are null.
+fn record(table: &VirtualTable, row: usize) -> Map<String, Value> {
+    (0..table.num_columns())
+        .map(|col| (table.header(col).to_string(), json_value(table, col, row)))
+        .collect()
+}
+
+fn json_value(table: &VirtualTable, col: usize, row: usize) -> Value {
+    if table.is_null(col, row) {
+        return Value::Null;
+    }
+    let cell = table.cell(col, row);
+    let data_row = table.data_row(row);
+    let value = match &table.data.typed[table.data_column(col)] {
+        TypedColumn::Integer(v) => v[data_row].map(Value::from),
+        TypedColumn::Float(v) => v[data_row].and_then(Number::from_f64).map(Value::Number),
+        TypedColumn::Boolean(v) => v[data_row].map(Value::Bool),
+        TypedColumn::Date(v) => v[data_row].map(|_| Value::String(cell.to_string())),
+        TypedColumn::DateTime(v) => v[data_row].map(|_| Value::String(cell.to_string())),
+        TypedColumn::String => return Value::String(cell.to_string()),
+    };
+    match value {
+        Some(value) => value,
+        None if cell.is_empty() => Value::Null,
+        // Unparseable cells are written as they are rather than dropped.
+        None => Value::String(cell.to_string()),
+    }
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_loader::{get_loader, TableData};
+
+    fn table() -> VirtualTable {
+        let columns = vec![
+            vec!["1".to_string(), "2".to_string()],
+            vec!["Smith, \"J\" <a>".to_string(), String::new()],
+            vec!["2.5".to_string(), "10".to_string()],
+            vec!["true".to_string(), "false".to_string()],
+        ];
+        let headers = ["id", "name", "price", "ok"].map(String::from).to_vec();
+        let nulls = vec![Vec::new(), vec![false, true]];
+        VirtualTable::new(TableData::with_nulls(headers, columns, nulls))
+    }
+
+    fn written(extension: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        get_writer(extension).unwrap().write(&table(), &mut out).unwrap();
+        out
+    }
+
+    fn round_trip(extension: &str) -> TableData {
+        let reader = Box::new(std::io::Cursor::new(written(extension)));
+        get_loader(extension).unwrap().load_reader(reader).unwrap()
+    }
+
+    #[test]
+    fn delimited_formats_read_back_the_same() {
+        for extension in ["csv", "tsv", "psv"] {
+            let data = round_trip(extension);
+            assert_eq!(data.headers, ["id", "name", "price", "ok"], "{}", extension);
+            assert_eq!(data.columns[1], ["Smith, \"J\" <a>", ""], "{}", extension);
+            assert_eq!(data.columns[2], ["2.5", "10"], "{}", extension);
+        }
+        assert_eq!(written("csv")[..31], *b"id,name,price,ok\n1,\"Smith, \"\"J\"");
+    }
+
+    #[test]
+    fn json_formats_read_back_with_types_and_nulls() {
+        for extension in ["json", "jsonl"] {
+            let data = round_trip(extension);
+            assert_eq!(data.headers, ["id", "name", "price", "ok"], "{}", extension);
+            assert_eq!(data.columns[0], ["1", "2"], "{}", extension);
+            assert_eq!(data.columns[1][0], "Smith, \"J\" <a>", "{}", extension);
+            assert!(data.is_null(1, 1), "{}", extension);
+            assert_eq!(data.columns[3], ["true", "false"], "{}", extension);
+        }
+        let lines = String::from_utf8(written("jsonl")).unwrap();
+        let first = r#"{"id":1,"name":"Smith, \"J\" <a>","price":2.5,"ok":true}"#;
+        assert!(lines.starts_with(first));
+    }
+
+    #[test]
+    fn markdown_and_html_escape_and_align() {
+        let markdown = String::from_utf8(written("md")).unwrap();
+        assert_eq!(
+            markdown,
+            "| id | name | price | ok |\n| ---: | --- | ---: | --- |\n\
+             | 1 | Smith, \"J\" <a> | 2.5 | true |\n| 2 |  | 10 | false |\n"
+        );
+        let html = String::from_utf8(written("html")).unwrap();
+        assert!(html.contains("<td>Smith, &quot;J&quot; &lt;a&gt;</td>"));
+        assert!(html.contains("<th style=\"text-align: right\">id</th>"));
+    }
+
+    #[test]
+    fn unknown_extensions_are_refused() {
+        assert!(writer_for_path("out.xyz").is_err());
+        assert!(writer_for_path("out").is_err());
+    }
+}
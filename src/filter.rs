@@ -0,0 +1,558 @@
+// src/filter.rs
+//
+// Row filters written as expressions over column names, such as
+//
+//     amount > 100 and country == "DE"
+//     name ~ /^a/i or not (`unit price` <= 2.5)
+//     closed_at == null
+//
+// Columns are compared by their type: numbers as numbers, dates and
+// datetimes in time order. Cells of text columns are compared as numbers
+// when both sides read as one, and as text otherwise. Comparisons with a
+// null or empty cell are false; `== null` and `!= null` test for those.
+
+use std::cmp::Ordering;
+use std::error::Error;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::column_type::{parse_boolean, parse_datetime, parse_float, TypedValue};
+use crate::data_loader::TableData;
+
+
+/// A parsed filter, ready to be evaluated against the rows of a table.
+#[derive(Clone)]
+pub struct Filter {
+    /// The expression as it was typed.
+    pub text: String,
+    expr: Expr,
+}
+
+impl Filter {
+    /// Parses `text`, resolving column names against the headers of `data`.
+    pub fn parse(text: &str, data: &TableData) -> Result<Self, Box<dyn Error>> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            headers: &data.headers,
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("Unexpected {} in filter", token.describe()).into());
+        }
+        Ok(Filter {
+            text: text.to_string(),
+            expr,
+        })
+    }
+
+    pub fn matches(&self, data: &TableData, row: usize) -> bool {
+        self.expr.eval(data, row)
+    }
+
+    /// Those of `rows` the filter keeps, in order.
+    pub fn matching_rows(
+        &self,
+        data: &TableData,
+        rows: impl IntoIterator<Item = usize>,
+    ) -> Vec<usize> {
+        rows.into_iter()
+            .filter(|&row| self.matches(data, row))
+            .collect()
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Number(String),
+    Regex(String, bool),
+    Op(CompareOp),
+    And,
+    Or,
+    Not,
+    True,
+    False,
+    Null,
+    LeftParen,
+    RightParen,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Ident(name) => format!("'{}'", name),
+            Token::Str(s) => format!("\"{}\"", s),
+            Token::Number(n) => n.clone(),
+            Token::Regex(r, _) => format!("/{}/", r),
+            Token::Op(op) => format!("'{}'", op.symbol()),
+            Token::And => "'and'".to_string(),
+            Token::Or => "'or'".to_string(),
+            Token::Not => "'not'".to_string(),
+            Token::True => "'true'".to_string(),
+            Token::False => "'false'".to_string(),
+            Token::Null => "'null'".to_string(),
+            Token::LeftParen => "'('".to_string(),
+            Token::RightParen => "')'".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+    NotMatch,
+}
+
+impl CompareOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            CompareOp::Eq => "==",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+            CompareOp::Match => "~",
+            CompareOp::NotMatch => "!~",
+        }
+    }
+
+    fn accepts(self, ordering: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ordering.is_eq(),
+            CompareOp::Ne => ordering.is_ne(),
+            CompareOp::Lt => ordering.is_lt(),
+            CompareOp::Le => ordering.is_le(),
+            CompareOp::Gt => ordering.is_gt(),
+            CompareOp::Ge => ordering.is_ge(),
+            CompareOp::Match | CompareOp::NotMatch => false,
+        }
+    }
+}
+
+
+fn tokenize(text: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    // Reads up to an unescaped `end`, leaving `i` just past it.
+    let delimited = |i: &mut usize, end: char, keep_escapes: bool| -> Option<String> {
+        let mut value = String::new();
+        *i += 1;
+        while *i < chars.len() {
+            match chars[*i] {
+                '\\' if *i + 1 < chars.len() => {
+                    let next = chars[*i + 1];
+                    // Regexes keep their escapes, apart from the one for the delimiter.
+                    if keep_escapes && next != end {
+                        value.push('\\');
+                    }
+                    value.push(next);
+                    *i += 2;
+                }
+                c if c == end => {
+                    *i += 1;
+                    return Some(value);
+                }
+                c => {
+                    value.push(c);
+                    *i += 1;
+                }
+            }
+        }
+        None
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LeftParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RightParen);
+                i += 1;
+            }
+            '"' | '\'' => {
+                let value = delimited(&mut i, c, false).ok_or("Unterminated string in filter")?;
+                tokens.push(Token::Str(value));
+            }
+            '`' => {
+                let name = delimited(&mut i, '`', false).ok_or("Unterminated `column` in filter")?;
+                tokens.push(Token::Ident(name));
+            }
+            '/' => {
+                let pattern = delimited(&mut i, '/', true).ok_or("Unterminated /regex/ in filter")?;
+                let case_insensitive = chars.get(i) == Some(&'i');
+                if case_insensitive {
+                    i += 1;
+                }
+                tokens.push(Token::Regex(pattern, case_insensitive));
+            }
+            '=' | '!' | '<' | '>' | '~' => {
+                let (op, len) = match (c, next) {
+                    ('=', Some('=')) => (Some(CompareOp::Eq), 2),
+                    ('=', _) => (Some(CompareOp::Eq), 1),
+                    ('!', Some('=')) => (Some(CompareOp::Ne), 2),
+                    ('!', Some('~')) => (Some(CompareOp::NotMatch), 2),
+                    ('!', _) => (None, 1),
+                    ('<', Some('=')) => (Some(CompareOp::Le), 2),
+                    ('<', Some('>')) => (Some(CompareOp::Ne), 2),
+                    ('<', _) => (Some(CompareOp::Lt), 1),
+                    ('>', Some('=')) => (Some(CompareOp::Ge), 2),
+                    ('>', _) => (Some(CompareOp::Gt), 1),
+                    _ => (Some(CompareOp::Match), 1),
+                };
+                tokens.push(op.map_or(Token::Not, Token::Op));
+                i += len;
+            }
+            '&' if next == Some('&') => {
+                tokens.push(Token::And);
+                i += 2;
+            }
+            '|' if next == Some('|') => {
+                tokens.push(Token::Or);
+                i += 2;
+            }
+            _ if c.is_ascii_digit()
+                || (matches!(c, '-' | '+' | '.') && next.is_some_and(|n| n.is_ascii_digit())) =>
+            {
+                let start = i;
+                i += 1;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || matches!(chars[i], '.' | '-' | ':' | '+'))
+                {
+                    i += 1;
+                }
+                // Dates and times written bare, like 2024-01-31, are kept whole.
+                tokens.push(Token::Number(chars[start..i].iter().collect()));
+            }
+            _ if c.is_alphanumeric() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '.'))
+                {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                tokens.push(match word.to_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "true" => Token::True,
+                    "false" => Token::False,
+                    "null" => Token::Null,
+                    _ => Token::Ident(word),
+                });
+            }
+            _ => return Err(format!("Unexpected '{}' in filter", c).into()),
+        }
+    }
+    Ok(tokens)
+}
+
+
+#[derive(Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Operand, CompareOp, Operand),
+    IsNull(usize, bool),
+    Matches(Operand, Regex, bool),
+}
+
+#[derive(Clone)]
+enum Operand {
+    Column(usize),
+    Literal(Literal),
+}
+
+/// A constant, read every way a cell might need to be compared with it.
+#[derive(Clone)]
+struct Literal {
+    text: String,
+    number: Option<f64>,
+    boolean: Option<bool>,
+    datetime: Option<chrono::NaiveDateTime>,
+}
+
+impl Literal {
+    fn new(text: String) -> Self {
+        Literal {
+            number: parse_float(&text),
+            boolean: parse_boolean(&text),
+            datetime: parse_datetime(&text),
+            text,
+        }
+    }
+
+    /// This constant as the same kind of value as `like`, if it reads as one.
+    fn as_value_like(&self, like: TypedValue) -> Option<TypedValue> {
+        match like {
+            TypedValue::Number(_) => self.number.map(TypedValue::Number),
+            TypedValue::Boolean(_) => self.boolean.map(TypedValue::Boolean),
+            TypedValue::DateTime(_) => self.datetime.map(TypedValue::DateTime),
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    headers: &'a [String],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let matched = self.peek() == Some(token);
+        if matched {
+            self.position += 1;
+        }
+        matched
+    }
+
+    fn or(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let mut expr = self.not()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, Box<dyn Error>> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        if self.eat(&Token::LeftParen) {
+            let expr = self.or()?;
+            if !self.eat(&Token::RightParen) {
+                return Err("Missing ')' in filter".into());
+            }
+            return Ok(expr);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let left = self.operand()?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            Some(token) => {
+                return Err(format!("Expected a comparison, found {}", token.describe()).into())
+            }
+            None => return Err("Expected a comparison at the end of the filter".into()),
+        };
+
+        if matches!(op, CompareOp::Match | CompareOp::NotMatch) {
+            let negated = op == CompareOp::NotMatch;
+            let regex = match self.next() {
+                Some(Token::Regex(pattern, case_insensitive)) => {
+                    RegexBuilder::new(&pattern).case_insensitive(case_insensitive).build()
+                }
+                Some(Token::Str(pattern)) => Regex::new(&pattern),
+                _ => return Err(format!("Expected a /regex/ after '{}'", op.symbol()).into()),
+            }
+            .map_err(|e| {
+                // The regex crate explains errors over several lines; the last one says what is wrong.
+                let message = e.to_string();
+                let reason = message.lines().last().unwrap_or_default();
+                format!("Invalid regex: {}", reason.trim_start_matches("error: "))
+            })?;
+            return Ok(Expr::Matches(left, regex, negated));
+        }
+
+        if self.eat(&Token::Null) {
+            return match (left, op) {
+                (Operand::Column(col), CompareOp::Eq) => Ok(Expr::IsNull(col, true)),
+                (Operand::Column(col), CompareOp::Ne) => Ok(Expr::IsNull(col, false)),
+                _ => Err("null can only be tested with == or != on a column".into()),
+            };
+        }
+
+        let right = self.operand()?;
+        Ok(Expr::Compare(left, op, right))
+    }
+
+    fn operand(&mut self) -> Result<Operand, Box<dyn Error>> {
+        match self.next() {
+            Some(Token::Ident(name)) => self.column(&name).map(Operand::Column),
+            Some(Token::Str(text)) | Some(Token::Number(text)) => {
+                Ok(Operand::Literal(Literal::new(text)))
+            }
+            Some(Token::True) => Ok(Operand::Literal(Literal::new("true".to_string()))),
+            Some(Token::False) => Ok(Operand::Literal(Literal::new("false".to_string()))),
+            Some(token) => Err(format!("Expected a column or value, found {}", token.describe()).into()),
+            None => Err("Unexpected end of filter".into()),
+        }
+    }
+
+    /// Looks a column up by name, falling back to a case-insensitive match.
+    fn column(&self, name: &str) -> Result<usize, Box<dyn Error>> {
+        self.headers
+            .iter()
+            .position(|h| h == name)
+            .or_else(|| self.headers.iter().position(|h| h.eq_ignore_ascii_case(name)))
+            .ok_or_else(|| format!("Unknown column '{}'", name).into())
+    }
+}
+
+
+impl Expr {
+    fn eval(&self, data: &TableData, row: usize) -> bool {
+        match self {
+            Expr::And(a, b) => a.eval(data, row) && b.eval(data, row),
+            Expr::Or(a, b) => a.eval(data, row) || b.eval(data, row),
+            Expr::Not(a) => !a.eval(data, row),
+            Expr::IsNull(col, is_null) => is_missing(data, *col, row) == *is_null,
+            Expr::Matches(operand, regex, negated) => match operand_text(data, operand, row) {
+                Some(text) => regex.is_match(text) != *negated,
+                None => false,
+            },
+            Expr::Compare(left, op, right) => {
+                compare(data, left, right, row).is_some_and(|ordering| op.accepts(ordering))
+            }
+        }
+    }
+}
+
+fn is_missing(data: &TableData, col: usize, row: usize) -> bool {
+    data.is_null(col, row) || data.columns[col][row].trim().is_empty()
+}
+
+fn operand_text<'a>(data: &'a TableData, operand: &'a Operand, row: usize) -> Option<&'a str> {
+    match operand {
+        Operand::Column(col) if is_missing(data, *col, row) => None,
+        Operand::Column(col) => Some(&data.columns[*col][row]),
+        Operand::Literal(literal) => Some(&literal.text),
+    }
+}
+
+/// Orders two operands for one row, or `None` when a cell is missing.
+fn compare(data: &TableData, left: &Operand, right: &Operand, row: usize) -> Option<Ordering> {
+    let left_text = operand_text(data, left, row)?;
+    let right_text = operand_text(data, right, row)?;
+
+    let typed = match (left, right) {
+        (Operand::Column(a), Operand::Column(b)) => {
+            match (data.typed[*a].value(row), data.typed[*b].value(row)) {
+                (Some(x), Some(y)) => compare_values(x, y),
+                _ => None,
+            }
+        }
+        (Operand::Column(col), Operand::Literal(literal)) => data.typed[*col]
+            .value(row)
+            .and_then(|x| literal.as_value_like(x).and_then(|y| compare_values(x, y))),
+        (Operand::Literal(literal), Operand::Column(col)) => data.typed[*col]
+            .value(row)
+            .and_then(|y| literal.as_value_like(y).and_then(|x| compare_values(x, y))),
+        (Operand::Literal(_), Operand::Literal(_)) => None,
+    };
+    Some(typed.unwrap_or_else(|| compare_text(left_text, right_text)))
+}
+
+/// Values of different kinds, such as a number and a date, do not compare.
+fn compare_values(a: TypedValue, b: TypedValue) -> Option<Ordering> {
+    match (a, b) {
+        (TypedValue::Number(x), TypedValue::Number(y)) => Some(x.total_cmp(&y)),
+        (TypedValue::Boolean(x), TypedValue::Boolean(y)) => Some(x.cmp(&y)),
+        (TypedValue::DateTime(x), TypedValue::DateTime(y)) => Some(x.cmp(&y)),
+        _ => None,
+    }
+}
+
+/// Numbers when both sides read as one, text otherwise.
+fn compare_text(a: &str, b: &str) -> Ordering {
+    match (parse_float(a.trim()), parse_float(b.trim())) {
+        (Some(x), Some(y)) => x.total_cmp(&y),
+        _ => a.cmp(b),
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> TableData {
+        let headers = vec!["n".to_string(), "name".to_string(), "closed".to_string()];
+        let columns = vec![
+            vec!["1", "2", "3", "4"],
+            vec!["a", "b", "a", "b"],
+            vec!["2024-01-01", "", "", "2024-03-01"],
+        ];
+        let columns = columns
+            .into_iter()
+            .map(|cells| cells.into_iter().map(String::from).collect())
+            .collect();
+        let nulls = vec![Vec::new(), Vec::new(), vec![false, false, true, false]];
+        TableData::with_nulls(headers, columns, nulls)
+    }
+
+    fn rows(text: &str) -> Vec<usize> {
+        let data = table();
+        Filter::parse(text, &data).unwrap().matching_rows(&data, 0..data.num_rows())
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(rows("n == 1 or name == \"b\" and n > 2"), vec![0, 3]);
+        assert_eq!(rows("(n == 1 or name == \"b\") and n > 2"), vec![3]);
+    }
+
+    #[test]
+    fn not_applies_to_the_next_comparison() {
+        assert_eq!(rows("not n > 2 and name == \"a\""), vec![0]);
+        assert_eq!(rows("not (n > 2 and name == \"a\")"), vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn null_tests_match_null_and_empty_cells() {
+        assert_eq!(rows("closed == null"), vec![1, 2]);
+        assert_eq!(rows("closed != null"), vec![0, 3]);
+    }
+
+    #[test]
+    fn comparisons_with_missing_cells_are_false() {
+        assert_eq!(rows("closed < 2024-02-01"), vec![0]);
+        assert_eq!(rows("closed != 2024-01-01"), vec![3]);
+    }
+
+    #[test]
+    fn unknown_columns_are_rejected() {
+        assert!(Filter::parse("missing > 1", &table()).is_err());
+        assert!(Filter::parse("n > 1 n", &table()).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+// src/group_by.rs
+
+use std::collections::HashMap;
+
+use crate::aggregation::{
+    aggregate_column, AggregationFunction, AggregationResult, UnparseablePolicy,
+};
+use crate::data_loader::TableData;
+
+
+/// Splits `rows` into groups sharing the same values in the key columns.
+/// Groups come out ordered by their key values; rows keep their order.
+pub fn group_rows(
+    data: &TableData,
+    keys: &[usize],
+    rows: impl IntoIterator<Item = usize>,
+) -> Vec<Vec<usize>> {
+    let mut index: HashMap<Vec<Option<&str>>, usize> = HashMap::new();
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for row in rows {
+        // Nulls form a group of their own rather than joining the empty strings.
+        let key = keys
+            .iter()
+            .map(|&col| (!data.is_null(col, row)).then(|| data.columns[col][row].as_str()))
+            .collect();
+        let group = *index.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(row);
+    }
+
+    groups.sort_by(|a, b| {
+        keys.iter()
+            .map(|&col| data.typed[col].compare(&data.columns[col], a[0], b[0]))
+            .find(|ord| ord.is_ne())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    groups
+}
+
+
+/// A summary with one row per group: the key values, the number of rows and
+/// the requested aggregations. Also returns how many cells were skipped or
+/// made an aggregation fail because they could not be parsed.
+pub fn group_table(
+    data: &TableData,
+    keys: &[usize],
+    groups: &[Vec<usize>],
+    aggregations: &[(usize, Vec<AggregationFunction>)],
+    policy: UnparseablePolicy,
+) -> (TableData, usize) {
+    let mut headers: Vec<String> = keys.iter().map(|&col| data.headers[col].clone()).collect();
+    headers.push("Count".to_string());
+    for (col, aggs) in aggregations {
+        for agg in aggs {
+            headers.push(format!("{:?}({})", agg, data.headers[*col]));
+        }
+    }
+
+    let mut columns: Vec<Vec<String>> = vec![Vec::with_capacity(groups.len()); headers.len()];
+    let mut nulls: Vec<Vec<bool>> = vec![Vec::with_capacity(groups.len()); headers.len()];
+    let mut unparseable = 0;
+    for rows in groups {
+        for (i, &col) in keys.iter().enumerate() {
+            columns[i].push(data.columns[col][rows[0]].clone());
+            nulls[i].push(data.is_null(col, rows[0]));
+        }
+        columns[keys.len()].push(rows.len().to_string());
+        nulls[keys.len()].push(false);
+
+        let mut out = keys.len() + 1;
+        for (col, aggs) in aggregations {
+            let results = aggregate_column(data, *col, rows.iter().copied(), aggs, policy);
+            for agg in aggs {
+                // Cells hold bare values so that the sheet types and sorts them as numbers.
+                let value = match &results[agg] {
+                    AggregationResult::Value { value, skipped } => {
+                        unparseable += skipped;
+                        value.clone()
+                    }
+                    AggregationResult::Failed { unparseable: count } => {
+                        unparseable += count;
+                        None
+                    }
+                };
+                nulls[out].push(value.is_none());
+                columns[out].push(value.unwrap_or_default());
+                out += 1;
+            }
+        }
+    }
+
+    (TableData::with_nulls(headers, columns, nulls), unparseable)
+}
+
+
+/// Width of the longest bar in a frequency histogram, in characters.
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Partial blocks for drawing bar ends at an eighth of a character.
+const BAR_EIGHTHS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+/// Lists each distinct value of a column with its count, share of the
+/// `total` rows and a bar histogram. `groups` are the rows holding each
+/// value, as from `group_rows`, and are reordered most frequent first.
+pub fn frequency_table(
+    data: &TableData,
+    col: usize,
+    groups: &mut [Vec<usize>],
+    total: usize,
+) -> TableData {
+    groups.sort_by_key(|rows| std::cmp::Reverse(rows.len()));
+
+    let total = total.max(1) as f64;
+    let largest = groups.first().map_or(1, Vec::len) as f64;
+
+    let mut values = Vec::with_capacity(groups.len());
+    let mut value_nulls = Vec::with_capacity(groups.len());
+    let mut counts = Vec::with_capacity(groups.len());
+    let mut percents = Vec::with_capacity(groups.len());
+    let mut bars = Vec::with_capacity(groups.len());
+    for rows in groups.iter() {
+        values.push(data.columns[col][rows[0]].clone());
+        value_nulls.push(data.is_null(col, rows[0]));
+        counts.push(rows.len().to_string());
+        percents.push(format!("{:.2}", rows.len() as f64 * 100.0 / total));
+        bars.push(bar(rows.len() as f64 / largest));
+    }
+
+    TableData::with_nulls(
+        vec![
+            data.headers[col].clone(),
+            "Count".to_string(),
+            "Percent".to_string(),
+            "Histogram".to_string(),
+        ],
+        vec![values, counts, percents, bars],
+        vec![value_nulls],
+    )
+}
+
+fn bar(fraction: f64) -> String {
+    let eighths = (fraction * (HISTOGRAM_WIDTH * 8) as f64).round() as usize;
+    let mut bar = "█".repeat(eighths / 8);
+    if !eighths.is_multiple_of(8) {
+        bar.push(BAR_EIGHTHS[eighths % 8]);
+    }
+    bar
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::column_type::ColumnType;
+
+    /// Countries with a null in row 1 and an empty cell in row 3.
+    fn sales() -> TableData {
+        let countries = ["DE", "", "FR", "", "DE", "FR", "DE"];
+        let amounts = ["10", "1", "5", "2", "20", "x", "30"];
+        let columns = vec![
+            countries.iter().map(|c| c.to_string()).collect(),
+            amounts.iter().map(|a| a.to_string()).collect(),
+        ];
+        let nulls = vec![vec![false, true, false, false, false, false, false]];
+        let mut data =
+            TableData::with_nulls(vec!["country".into(), "amount".into()], columns, nulls);
+        data.set_column_type(1, ColumnType::Integer);
+        data
+    }
+
+    #[test]
+    fn nulls_group_apart_from_empty_cells() {
+        let data = sales();
+        let groups = group_rows(&data, &[0], 0..data.num_rows());
+        assert_eq!(groups, [vec![1], vec![3], vec![0, 4, 6], vec![2, 5]]);
+    }
+
+    #[test]
+    fn group_table_aggregates_each_group() {
+        let data = sales();
+        let groups = group_rows(&data, &[0], [0, 2, 4, 5, 6]);
+        let aggregations = [(1, vec![AggregationFunction::Sum, AggregationFunction::Max])];
+        let (table, unparseable) =
+            group_table(&data, &[0], &groups, &aggregations, UnparseablePolicy::Skip);
+        assert_eq!(table.headers, ["country", "Count", "Sum(amount)", "Max(amount)"]);
+        assert_eq!(table.columns[0], ["DE", "FR"]);
+        assert_eq!(table.columns[1], ["3", "2"]);
+        assert_eq!(table.columns[2], ["60", "5"]);
+        assert_eq!(table.columns[3], ["30", "5"]);
+        assert_eq!(unparseable, 2);
+
+        let (table, _) = group_table(&data, &[0], &groups, &aggregations, UnparseablePolicy::Fail);
+        assert!(table.is_null(2, 1));
+        assert!(!table.is_null(2, 0));
+    }
+
+    #[test]
+    fn frequency_table_puts_the_most_common_first() {
+        let data = sales();
+        let mut groups = group_rows(&data, &[0], 0..data.num_rows());
+        let table = frequency_table(&data, 0, &mut groups, data.num_rows());
+        assert_eq!(table.columns[0], ["DE", "FR", "", ""]);
+        assert_eq!(table.columns[1], ["3", "2", "1", "1"]);
+        assert_eq!(table.columns[2][0], "42.86");
+        assert!(table.is_null(0, 2));
+        assert!(!table.is_null(0, 3));
+        assert_eq!(table.columns[3][0].chars().count(), HISTOGRAM_WIDTH);
+    }
+}
@@ -0,0 +1,218 @@
+// src/json_loader.rs
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read};
+
+use serde_json::{Map, Value};
+
+use crate::data_loader::{collect_chunks, DataLoader, TableData, CHUNK_ROWS};
+
+/// Column name used for records that are plain values rather than objects.
+const SCALAR_COLUMN: &str = "value";
+
+
+/// Loads a JSON document holding an array of objects.
+pub struct JsonLoader;
+
+impl DataLoader for JsonLoader {
+    fn load_reader(&self, reader: Box<dyn Read>) -> Result<TableData, Box<dyn Error>> {
+        let reader = BufReader::new(reader);
+        let document: Value = serde_json::from_reader(reader)?;
+
+        let mut builder = RecordTableBuilder::default();
+        match document {
+            Value::Array(records) => {
+                for record in records {
+                    builder.push(record);
+                }
+            }
+            other => builder.push(other),
+        }
+
+        Ok(builder.take_chunk())
+    }
+}
+
+
+/// Loads JSON Lines: one JSON value per line, blank lines ignored.
+pub struct JsonlLoader;
+
+impl DataLoader for JsonlLoader {
+    fn load_reader(&self, reader: Box<dyn Read>) -> Result<TableData, Box<dyn Error>> {
+        collect_chunks(|on_chunk| self.stream_reader(reader, on_chunk))
+    }
+
+    fn stream_reader(
+        &self,
+        reader: Box<dyn Read>,
+        on_chunk: &mut dyn FnMut(TableData) -> bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let reader = BufReader::new(reader);
+
+        let mut builder = RecordTableBuilder::default();
+        for (line_number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Value = serde_json::from_str(&line)
+                .map_err(|e| format!("Line {}: {}", line_number + 1, e))?;
+            builder.push(record);
+
+            if builder.rows >= CHUNK_ROWS && !on_chunk(builder.take_chunk()) {
+                return Ok(());
+            }
+        }
+
+        on_chunk(builder.take_chunk());
+        Ok(())
+    }
+}
+
+
+/// Collects flattened records into columns, adding a column the first time a
+/// key is seen and back-filling it with empty cells for earlier records.
+/// Explicit JSON nulls are flagged so they display differently from missing keys.
+///
+/// Keys that flatten to a name already taken in the same record, as `a.b`
+/// does in `{"a.b": 1, "a": {"b": 2}}`, get a numbered column, `a.b (2)`.
+#[derive(Default)]
+struct RecordTableBuilder {
+    headers: Vec<String>,
+    columns: Vec<Vec<String>>,
+    nulls: Vec<Vec<bool>>,
+    index: HashMap<String, usize>,
+    rows: usize,
+}
+
+impl RecordTableBuilder {
+    fn push(&mut self, record: Value) {
+        let mut fields = Vec::new();
+        match record {
+            Value::Object(map) => flatten_object("", map, &mut fields),
+            other => fields.push((SCALAR_COLUMN.to_string(), other)),
+        }
+
+        let mut taken = HashSet::new();
+        for (key, value) in fields {
+            let key = if taken.contains(&key) {
+                (2..)
+                    .map(|n| format!("{} ({})", key, n))
+                    .find(|name| !taken.contains(name))
+                    .expect("unbounded range")
+            } else {
+                key
+            };
+            taken.insert(key.clone());
+            let col = self.column_index(key);
+            let column = &mut self.columns[col];
+            column.resize(self.rows, String::new());
+            let flags = &mut self.nulls[col];
+            flags.resize(self.rows, false);
+            flags.push(value.is_null());
+            column.push(render_value(value));
+        }
+
+        self.rows += 1;
+    }
+
+    fn column_index(&mut self, key: String) -> usize {
+        if let Some(&col) = self.index.get(&key) {
+            return col;
+        }
+        let col = self.headers.len();
+        self.index.insert(key.clone(), col);
+        self.headers.push(key);
+        self.columns.push(Vec::new());
+        self.nulls.push(Vec::new());
+        col
+    }
+
+    /// Hands over the rows pushed since the last call, under every header seen so far.
+    fn take_chunk(&mut self) -> TableData {
+        let columns = self
+            .columns
+            .iter_mut()
+            .map(|column| {
+                column.resize(self.rows, String::new());
+                std::mem::take(column)
+            })
+            .collect();
+        let nulls = self
+            .nulls
+            .iter_mut()
+            .map(|flags| {
+                flags.resize(self.rows, false);
+                std::mem::take(flags)
+            })
+            .collect();
+        self.rows = 0;
+        TableData::with_nulls(self.headers.clone(), columns, nulls)
+    }
+}
+
+/// Expands nested objects into `parent.child` keys; everything else is a leaf.
+fn flatten_object(prefix: &str, map: Map<String, Value>, out: &mut Vec<(String, Value)>) {
+    for (key, value) in map {
+        let name = if prefix.is_empty() {
+            key
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            Value::Object(inner) if !inner.is_empty() => flatten_object(&name, inner, out),
+            other => out.push((name, other)),
+        }
+    }
+}
+
+fn render_value(value: Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s,
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        other => other.to_string(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(loader: &dyn DataLoader, text: &str) -> TableData {
+        let reader = Box::new(std::io::Cursor::new(text.to_string()));
+        loader.load_reader(reader).unwrap()
+    }
+
+    fn row(data: &TableData, row: usize) -> Vec<&str> {
+        data.columns.iter().map(|column| column[row].as_str()).collect()
+    }
+
+    #[test]
+    fn nested_objects_flatten_to_dotted_names() {
+        let data = load(&JsonLoader, r#"[{"id": 1, "user": {"name": "a", "geo": {"lat": 2}}}]"#);
+        assert_eq!(data.headers, ["id", "user.name", "user.geo.lat"]);
+        assert_eq!(row(&data, 0), ["1", "a", "2"]);
+    }
+
+    #[test]
+    fn keys_seen_later_are_back_filled_and_nulls_flagged() {
+        let data = load(&JsonlLoader, "{\"a\": 1}\n\n{\"a\": null, \"b\": [1, 2]}\n");
+        assert_eq!(data.headers, ["a", "b"]);
+        assert_eq!(row(&data, 0), ["1", ""]);
+        assert_eq!(row(&data, 1)[1], "[1,2]");
+        assert!(data.is_null(0, 1));
+        assert!(!data.is_null(1, 0));
+    }
+
+    #[test]
+    fn colliding_names_get_their_own_column() {
+        let data = load(&JsonlLoader, "{\"a.b\": 1, \"a\": {\"b\": 2}, \"c\": 3}\n");
+        assert_eq!(data.headers, ["a.b", "a.b (2)", "c"]);
+        assert_eq!(row(&data, 0), ["1", "2", "3"]);
+        assert_eq!(data.num_rows(), 1);
+    }
+}
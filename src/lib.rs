@@ -0,0 +1,86 @@
+// src/lib.rs
+
+//! Loading, sorting, filtering and aggregating tables, and the terminal
+//! viewer built on them.
+//!
+//! Tables are loaded into a [`TableData`] by a [`DataLoader`], picked by file
+//! extension with [`get_loader`] or for a path with [`Source`]. A
+//! [`VirtualTable`] is a view of shared data: sorting and filtering it only
+//! rearranges row indices. [`aggregate_column`] and the [`group_by`] module
+//! summarize the rows of a view, and a [`DataWriter`] writes one out.
+//!
+//! ```no_run
+//! use fastdata::{get_loader, Filter, SortKey, Collation, VirtualTable, DataWriter};
+//! use fastdata::data_writer::DelimitedWriter;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let data = get_loader("csv")?.load("sales.csv")?;
+//! let mut view = VirtualTable::new(data);
+//!
+//! let filter = Filter::parse("amount > 100", &view.data)?;
+//! let rows = filter.matching_rows(&view.data, view.rows().to_vec());
+//! view.set_rows(rows);
+//!
+//! let amount = view.data.headers.iter().position(|h| h == "amount").unwrap();
+//! view.sort_by(&[SortKey { column: amount, ascending: false }], Collation::Binary);
+//! DelimitedWriter::new().save(&view, "large_sales.csv")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`TuiApp`] is the interactive sheet. [`tui_app::run`] runs it with the
+//! sheets opened from it on any ratatui backend; to embed it in another
+//! application instead, feed it keys with [`TuiApp::handle_key`] and draw it
+//! with [`TuiApp::render`].
+
+/// Summary statistics over the rows of a column.
+pub mod aggregation;
+/// Loading on a background thread, with progress.
+pub mod background_loader;
+/// Filtering, sorting and aggregating without the TUI.
+pub mod batch;
+/// Column type inference and parsed column values.
+pub mod column_type;
+/// Transparent decompression of gzip, zstd, bzip2 and xz input.
+pub mod compression;
+/// The table model and the loader registry, with delimited text loading.
+pub mod data_loader;
+/// Writing views out as delimited text, JSON, Markdown or HTML.
+pub mod data_writer;
+/// Filter expressions over the columns of a table.
+pub mod filter;
+/// Grouping rows by key columns, and frequency tables.
+pub mod group_by;
+/// JSON and JSON Lines loading.
+pub mod json_loader;
+/// Parquet loading.
+pub mod parquet_loader;
+mod prompt;
+/// Text and regex search over a view.
+pub mod search;
+/// Multi-key sorting and text collations.
+pub mod sort;
+/// Opening files or standard input as tables or sheets.
+pub mod source;
+/// In-process SQL queries over open tables.
+pub mod sql;
+/// Excel and OpenDocument spreadsheet loading.
+pub mod spreadsheet_loader;
+/// SQLite table and query loading.
+pub mod sqlite_loader;
+/// The interactive sheet.
+pub mod tui_app;
+/// Views of shared tables.
+pub mod virtual_table;
+
+pub use aggregation::{aggregate_column, AggregationFunction, AggregationResult, UnparseablePolicy};
+pub use data_loader::{get_loader, DataLoader, TableData};
+pub use data_writer::{get_writer, DataWriter};
+pub use filter::Filter;
+pub use sort::{Collation, SortKey};
+pub use source::Source;
+pub use tui_app::{SheetAction, SheetKind, TuiApp};
+pub use virtual_table::VirtualTable;
+
+/// The crossterm the sheet reads keys with, for callers feeding it key events.
+pub use crossterm;
@@ -1,16 +1,13 @@
 // src/main.rs
 
-mod data_loader;
-mod virtual_table;
-mod tui_app;
-
-use data_loader::{get_loader};
-use virtual_table::VirtualTable;
-use tui_app::TuiApp;
+use fastdata::aggregation::UnparseablePolicy;
+use fastdata::batch::{self, BatchOptions};
+use fastdata::data_loader::{parse_delimiter, STDIN_PATH};
+use fastdata::{tui_app, Source};
 
 use std::env;
 use std::error::Error;
-use std::io::{self};
+use std::io::{self, IsTerminal};
 use crossterm::{
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
@@ -23,6 +20,9 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let mut file_path = String::new();
     let mut backend_ext = None;
+    let mut delimiter = None;
+    let mut query = None;
+    let mut batch = BatchOptions::default();
 
     let mut i = 1;
     while i < args.len() {
@@ -36,6 +36,43 @@ fn main() -> Result<(), Box<dyn Error>> {
                     return Ok(());
                 }
             }
+            "-d" => {
+                if i + 1 < args.len() {
+                    match parse_delimiter(&args[i + 1]) {
+                        Ok(d) => delimiter = Some(d),
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            return Ok(());
+                        }
+                    }
+                    i += 1;
+                } else {
+                    eprintln!("Error: '-d' option requires a delimiter character");
+                    return Ok(());
+                }
+            }
+            "--sql" => {
+                if i + 1 < args.len() {
+                    query = Some(args[i + 1].clone());
+                    i += 1;
+                } else {
+                    eprintln!("Error: '--sql' option requires a query");
+                    return Ok(());
+                }
+            }
+            "--filter" | "--sort" | "--collation" | "--agg" | "--out" | "--format" => {
+                if i + 1 < args.len() {
+                    if let Err(e) = batch.set(&args[i], &args[i + 1]) {
+                        eprintln!("Error: {}", e);
+                        return Ok(());
+                    }
+                    i += 1;
+                } else {
+                    eprintln!("Error: '{}' option requires a value", args[i]);
+                    return Ok(());
+                }
+            }
+            "--fail-unparseable" => batch.policy = UnparseablePolicy::Fail,
             _ => {
                 if file_path.is_empty() {
                     file_path = args[i].clone();
@@ -48,37 +85,49 @@ fn main() -> Result<(), Box<dyn Error>> {
         i += 1;
     }
 
+    // With nothing on the command line, piped input is read as if '-' was given.
+    if file_path.is_empty() && !io::stdin().is_terminal() {
+        file_path = STDIN_PATH.to_string();
+    }
+
     if file_path.is_empty() {
-        eprintln!("Usage: fastdata [-b format] <path_to_file>");
+        eprintln!("Usage: fastdata [-b format] [-d delimiter] [--sql query] <path_to_file | ->");
+        eprintln!(
+            "       fastdata [options] [--filter expr] [--sort col[:desc],...] \
+             [--collation binary|natural|nocase] [--agg col:func,...] [--fail-unparseable] \
+             [--out file] [--format ext] <path_to_file | ->"
+        );
         return Ok(());
     }
 
-
-    let extension = if let Some(ext) = backend_ext {
-        ext
-    } else {
-        std::path::Path::new(&file_path)
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .unwrap_or("")
-            .to_string()
+    let source = match Source::open(&file_path, backend_ext.as_deref(), delimiter, query) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
     };
 
+    // Batch mode never touches the terminal, so it works in pipelines and CI jobs.
+    if batch.is_set() {
+        if let Err(e) = source.load().and_then(|data| batch::run(data, &batch)) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-    let loader = match get_loader(&extension) {
-        Ok(loader) => loader,
+    let app = match source.open_sheet() {
+        Ok(app) => app,
         Err(e) => {
             eprintln!("Error: {}", e);
-            return Ok(());
+            std::process::exit(1);
         }
     };
 
 
-    let data = loader.load(&file_path)?;
-    let table = VirtualTable::new(data);
-    let app = TuiApp::new(table);
-
-
+    // When stdin is a pipe, crossterm switches the controlling terminal (/dev/tty)
+    // to raw mode and reads keys from it instead.
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen)?;
@@ -89,16 +138,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     terminal.hide_cursor()?;
 
 
-    let mut app_stack = vec![app];
-
-
-    while let Some(current_app) = app_stack.last_mut() {
-        if let Some(new_app) = current_app.main_loop(&mut terminal)? {
-            app_stack.push(new_app);
-        } else {
-            app_stack.pop();
-        }
-    }
+    tui_app::run(app, &mut terminal)?;
 
 
     terminal.show_cursor()?;
@@ -110,4 +150,5 @@ fn main() -> Result<(), Box<dyn Error>> {
         LeaveAlternateScreen,
     )?;
     Ok(())
-}
\ No newline at end of file
+}
+
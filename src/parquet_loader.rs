@@ -0,0 +1,106 @@
+// src/parquet_loader.rs
+
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+
+use bytes::Bytes;
+use chrono::DateTime;
+use parquet::file::reader::{ChunkReader, FileReader, SerializedFileReader};
+use parquet::record::Field;
+
+use crate::data_loader::{collect_chunks, DataLoader, TableData, CHUNK_ROWS};
+
+
+/// Loads every row group of a Parquet file using the record API, which
+/// decodes dictionary pages and definition levels for us.
+pub struct ParquetLoader;
+
+impl DataLoader for ParquetLoader {
+    fn load(&self, path: &str) -> Result<TableData, Box<dyn Error>> {
+        let reader = SerializedFileReader::new(File::open(path)?)?;
+        collect_chunks(|on_chunk| read_rows(reader, on_chunk))
+    }
+
+    fn load_reader(&self, reader: Box<dyn Read>) -> Result<TableData, Box<dyn Error>> {
+        collect_chunks(|on_chunk| self.stream_reader(reader, on_chunk))
+    }
+
+    /// Parquet keeps its metadata at the end of the file, so a stream is
+    /// buffered whole before rows can be decoded and handed over.
+    fn stream_reader(
+        &self,
+        mut reader: Box<dyn Read>,
+        on_chunk: &mut dyn FnMut(TableData) -> bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        read_rows(SerializedFileReader::new(Bytes::from(buffer))?, on_chunk)
+    }
+}
+
+fn read_rows<R: ChunkReader + 'static>(
+    reader: SerializedFileReader<R>,
+    on_chunk: &mut dyn FnMut(TableData) -> bool,
+) -> Result<(), Box<dyn Error>> {
+    let headers: Vec<String> = reader
+        .metadata()
+        .file_metadata()
+        .schema()
+        .get_fields()
+        .iter()
+        .map(|field| field.name().to_string())
+        .collect();
+
+    let new_chunk = || -> (Vec<Vec<String>>, Vec<Vec<bool>>) {
+        (
+            headers.iter().map(|_| Vec::with_capacity(CHUNK_ROWS)).collect(),
+            headers.iter().map(|_| Vec::with_capacity(CHUNK_ROWS)).collect(),
+        )
+    };
+    let (mut columns, mut nulls) = new_chunk();
+    let mut rows = 0;
+
+    for row in reader.get_row_iter(None)? {
+        let row = row?;
+        for (col, (_, field)) in row.get_column_iter().enumerate() {
+            let is_null = matches!(field, Field::Null);
+            columns[col].push(if is_null { String::new() } else { render_field(field) });
+            nulls[col].push(is_null);
+        }
+        rows += 1;
+
+        if rows == CHUNK_ROWS {
+            let (next_columns, next_nulls) = new_chunk();
+            let columns = std::mem::replace(&mut columns, next_columns);
+            let nulls = std::mem::replace(&mut nulls, next_nulls);
+            if !on_chunk(TableData::with_nulls(headers.clone(), columns, nulls)) {
+                return Ok(());
+            }
+            rows = 0;
+        }
+    }
+
+    on_chunk(TableData::with_nulls(headers, columns, nulls));
+    Ok(())
+}
+
+fn render_field(field: &Field) -> String {
+    match field {
+        Field::Str(s) => s.clone(),
+        Field::Bytes(bytes) => match std::str::from_utf8(bytes.data()) {
+            Ok(s) => s.to_string(),
+            Err(_) => bytes.data().iter().map(|b| format!("{:02x}", b)).collect(),
+        },
+        Field::Double(v) => v.to_string(),
+        Field::Float(v) => v.to_string(),
+        // The crate's own formatting drops sub-second precision and uses the local timezone.
+        Field::TimestampMillis(ms) => DateTime::from_timestamp_millis(*ms)
+            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S%.3f").to_string())
+            .unwrap_or_else(|| ms.to_string()),
+        Field::TimestampMicros(us) => DateTime::from_timestamp_micros(*us)
+            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S%.6f").to_string())
+            .unwrap_or_else(|| us.to_string()),
+        other => other.to_string(),
+    }
+}
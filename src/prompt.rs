@@ -0,0 +1,112 @@
+// src/prompt.rs
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::{Position, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    Frame,
+};
+
+pub enum PromptEvent {
+    /// The key was not one the prompt handles.
+    Ignored,
+    /// The cursor moved; the text is unchanged.
+    Moved,
+    Changed,
+    Submitted,
+    Cancelled,
+}
+
+/// A one-line text input drawn at the bottom of the screen.
+pub struct Prompt {
+    pub label: String,
+    pub input: String,
+    /// Cursor position, in characters.
+    cursor: usize,
+}
+
+impl Prompt {
+    pub fn new(label: impl Into<String>) -> Self {
+        Prompt {
+            label: label.into(),
+            input: String::new(),
+            cursor: 0,
+        }
+    }
+
+    pub fn with_input(mut self, input: impl Into<String>) -> Self {
+        self.input = input.into();
+        self.cursor = self.input.chars().count();
+        self
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> PromptEvent {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Enter => PromptEvent::Submitted,
+            KeyCode::Esc => PromptEvent::Cancelled,
+            KeyCode::Char('u') if control => {
+                self.input.clear();
+                self.cursor = 0;
+                PromptEvent::Changed
+            }
+            KeyCode::Char(c) if !control => {
+                let at = self.byte_index(self.cursor);
+                self.input.insert(at, c);
+                self.cursor += 1;
+                PromptEvent::Changed
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                let at = self.byte_index(self.cursor);
+                self.input.remove(at);
+                PromptEvent::Changed
+            }
+            KeyCode::Delete if self.cursor < self.input.chars().count() => {
+                let at = self.byte_index(self.cursor);
+                self.input.remove(at);
+                PromptEvent::Changed
+            }
+            KeyCode::Left => {
+                self.cursor = self.cursor.saturating_sub(1);
+                PromptEvent::Moved
+            }
+            KeyCode::Right => {
+                self.cursor = (self.cursor + 1).min(self.input.chars().count());
+                PromptEvent::Moved
+            }
+            KeyCode::Home => {
+                self.cursor = 0;
+                PromptEvent::Moved
+            }
+            KeyCode::End => {
+                self.cursor = self.input.chars().count();
+                PromptEvent::Moved
+            }
+            _ => PromptEvent::Ignored,
+        }
+    }
+
+    fn byte_index(&self, chars: usize) -> usize {
+        self.input
+            .char_indices()
+            .nth(chars)
+            .map_or(self.input.len(), |(i, _)| i)
+    }
+
+    /// Draws the label, the input and then `extra`, with the cursor in the input.
+    pub fn render(&self, f: &mut Frame, area: Rect, extra: Vec<Span<'static>>) {
+        let label = format!("{} ", self.label);
+        let cursor_x = area.x + (label.chars().count() + self.cursor) as u16;
+
+        let mut spans = vec![
+            Span::styled(label, Style::default().fg(Color::Yellow)),
+            Span::raw(self.input.clone()),
+            Span::raw("  "),
+        ];
+        spans.extend(extra);
+        f.render_widget(Line::from(spans), area);
+        f.set_cursor_position(Position::new(cursor_x.min(area.right().saturating_sub(1)), area.y));
+    }
+}
@@ -0,0 +1,105 @@
+// src/search.rs
+
+use regex::{Regex, RegexBuilder};
+
+use crate::virtual_table::VirtualTable;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchScope {
+    /// A data column, searched wherever it has been moved to in the view.
+    Column(usize),
+    Table,
+}
+
+/// What to look for, as set up in the search prompt.
+#[derive(Clone, Debug)]
+pub struct SearchOptions {
+    pub text: String,
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub scope: SearchScope,
+}
+
+impl SearchOptions {
+    /// Flags shown next to the prompt and in status messages.
+    pub fn describe(&self) -> String {
+        let scope = match self.scope {
+            SearchScope::Column(_) => "column",
+            SearchScope::Table => "table",
+        };
+        format!(
+            "[{}] [{}] [{}]",
+            if self.regex { "regex" } else { "text" },
+            if self.case_sensitive { "match case" } else { "ignore case" },
+            scope
+        )
+    }
+}
+
+
+/// A compiled search, matching cells anywhere in their text.
+pub struct Search {
+    pub options: SearchOptions,
+    pattern: Regex,
+}
+
+impl Search {
+    pub fn new(options: SearchOptions) -> Result<Self, regex::Error> {
+        let source = if options.regex {
+            options.text.clone()
+        } else {
+            regex::escape(&options.text)
+        };
+        let pattern = RegexBuilder::new(&source)
+            .case_insensitive(!options.case_sensitive)
+            .build()?;
+        Ok(Search { options, pattern })
+    }
+
+    /// Whether a cell is a hit. Null cells never are.
+    pub fn matches(&self, table: &VirtualTable, col: usize, row: usize) -> bool {
+        let in_scope = match self.options.scope {
+            SearchScope::Column(scope) => table.data_column(col) == scope,
+            SearchScope::Table => true,
+        };
+        in_scope && !table.is_null(col, row) && self.pattern.is_match(table.cell(col, row))
+    }
+
+    /// The next hit after `from`, or the previous one when going backwards,
+    /// going through the table row by row and wrapping around at the ends.
+    /// `from` itself is checked last, unless `include_start` is set.
+    pub fn find(
+        &self,
+        table: &VirtualTable,
+        from: (usize, usize),
+        forward: bool,
+        include_start: bool,
+    ) -> Option<(usize, usize)> {
+        let num_cols = table.num_columns();
+        let (first_col, width) = match self.options.scope {
+            SearchScope::Column(scope) => {
+                ((0..num_cols).position(|col| table.data_column(col) == scope)?, 1)
+            }
+            SearchScope::Table => (0, num_cols),
+        };
+        let total = table.num_rows() * width;
+        if total == 0 {
+            return None;
+        }
+
+        let (row, col) = from;
+        let start = (row * width + col.saturating_sub(first_col).min(width - 1)) % total;
+        let steps = if include_start { 0..total } else { 1..total + 1 };
+        steps
+            .map(|step| {
+                let position = if forward {
+                    (start + step) % total
+                } else {
+                    (start + total - step) % total
+                };
+                (position / width, first_col + position % width)
+            })
+            .find(|&(row, col)| self.matches(table, col, row))
+    }
+}
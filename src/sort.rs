@@ -0,0 +1,181 @@
+// src/sort.rs
+
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::column_type::TypedColumn;
+use crate::data_loader::TableData;
+
+
+/// How text cells are ordered. Typed columns always sort by their values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Collation {
+    /// Character by character, uppercase before lowercase.
+    #[default]
+    Binary,
+    /// Runs of digits compare as numbers, so file2 comes before file10.
+    Natural,
+    /// Case-insensitive: letters compare by their lowercase forms, with ties
+    /// in binary order. This is not a locale-aware collation; accented
+    /// letters still sort by code point.
+    CaseInsensitive,
+}
+
+impl Collation {
+    pub fn label(self) -> &'static str {
+        match self {
+            Collation::Binary => "binary",
+            Collation::Natural => "natural",
+            Collation::CaseInsensitive => "case-insensitive",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Collation::Binary => Collation::Natural,
+            Collation::Natural => Collation::CaseInsensitive,
+            Collation::CaseInsensitive => Collation::Binary,
+        }
+    }
+
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        match self {
+            Collation::Binary => a.cmp(b),
+            Collation::Natural => natural_cmp(a, b).then_with(|| a.cmp(b)),
+            Collation::CaseInsensitive => caseless_cmp(a, b).then_with(|| a.cmp(b)),
+        }
+    }
+}
+
+
+/// One column of a sort, by data column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: usize,
+    pub ascending: bool,
+}
+
+impl SortKey {
+    pub fn compare(&self, data: &TableData, collation: Collation, a: usize, b: usize) -> Ordering {
+        let cells = &data.columns[self.column];
+        let ord = match &data.typed[self.column] {
+            TypedColumn::String => collation.compare(&cells[a], &cells[b]),
+            typed => typed.compare(cells, a, b),
+        };
+        if self.ascending {
+            ord
+        } else {
+            ord.reverse()
+        }
+    }
+}
+
+/// Compares two rows by each key in turn.
+pub fn compare_rows(
+    data: &TableData,
+    keys: &[SortKey],
+    collation: Collation,
+    a: usize,
+    b: usize,
+) -> Ordering {
+    keys.iter()
+        .map(|key| key.compare(data, collation, a, b))
+        .find(|ord| ord.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+
+/// Compares the lowercase forms of two strings, for any script with case.
+fn caseless_cmp(a: &str, b: &str) -> Ordering {
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .cmp(b.chars().flat_map(char::to_lowercase))
+}
+
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let ord = compare_numbers(&digit_run(&mut a), &digit_run(&mut b));
+                if ord.is_ne() {
+                    return ord;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn digit_run(chars: &mut Peekable<Chars>) -> String {
+    let mut run = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        run.push(c);
+    }
+    run
+}
+
+/// Compares digit strings by value, however long they are.
+fn compare_numbers(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(collation: Collation, values: &[&str]) -> Vec<String> {
+        let mut values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        values.sort_by(|a, b| collation.compare(a, b));
+        values
+    }
+
+    #[test]
+    fn natural_order_compares_digit_runs_as_numbers() {
+        let values = ["a10", "a2", "a1b", "b1", "a02", "a", "a9999999999999999999999"];
+        assert_eq!(
+            sorted(Collation::Natural, &values),
+            ["a", "a1b", "a02", "a2", "a10", "a9999999999999999999999", "b1"]
+        );
+        assert_eq!(sorted(Collation::Binary, &["a10", "a2"]), ["a10", "a2"]);
+    }
+
+    #[test]
+    fn case_insensitive_order_folds_case() {
+        let values = ["banana", "Apple", "apple", "Cherry", "ÉCLAIR", "éclair"];
+        assert_eq!(
+            sorted(Collation::CaseInsensitive, &values),
+            ["Apple", "apple", "banana", "Cherry", "ÉCLAIR", "éclair"]
+        );
+        assert_eq!(sorted(Collation::Binary, &["banana", "Apple"]), ["Apple", "banana"]);
+    }
+
+    #[test]
+    fn sort_keys_order_typed_columns_by_value() {
+        let headers = vec!["n".to_string(), "s".to_string()];
+        let columns = vec![
+            ["10", "9", "10"].map(String::from).to_vec(),
+            ["b", "a", "a"].map(String::from).to_vec(),
+        ];
+        let data = TableData::new(headers, columns);
+        let keys = [
+            SortKey { column: 0, ascending: false },
+            SortKey { column: 1, ascending: true },
+        ];
+        let mut rows = vec![0, 1, 2];
+        rows.sort_by(|&a, &b| compare_rows(&data, &keys, Collation::Binary, a, b));
+        assert_eq!(rows, [2, 0, 1]);
+    }
+}
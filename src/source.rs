@@ -0,0 +1,205 @@
+// src/source.rs
+
+use std::error::Error;
+use std::path::Path;
+
+use tempfile::NamedTempFile;
+
+use crate::background_loader::{BackgroundLoad, CountingReader};
+use crate::compression::{decompress, strip_compression_suffix};
+use crate::data_loader::{
+    delimited_loader, get_loader, open_input, spool_to_temp_file, DataLoader, DelimitedLoader,
+    Dialect, TableData, STDIN_PATH,
+};
+use crate::spreadsheet_loader::{sheet_list_table, SpreadsheetLoader, WORKBOOK_EXTENSIONS};
+use crate::sqlite_loader::{SqliteLoader, DATABASE_EXTENSIONS};
+use crate::tui_app::{SheetKind, TuiApp};
+use crate::virtual_table::VirtualTable;
+
+
+/// A file or standard input to read a table from, with the format worked
+/// out from its name unless given.
+///
+/// Piped or compressed databases and workbooks are written out to a
+/// temporary file first, since they are read by path; it is removed when
+/// the source is dropped, so keep the source around while its sheets are open.
+pub struct Source {
+    /// The path as given, `-` for standard input.
+    pub path: String,
+    /// Lowercase extension picking the loader, such as `csv` or `db`.
+    pub extension: String,
+    pub delimiter: Option<u8>,
+    /// Query to run on a SQLite database instead of listing its tables.
+    pub query: Option<String>,
+    /// Where databases and workbooks are reopened from.
+    source_path: String,
+    _spooled_file: Option<NamedTempFile>,
+}
+
+impl Source {
+    /// `format` overrides the extension, and `delimiter` the sniffed one.
+    pub fn open(
+        path: &str,
+        format: Option<&str>,
+        delimiter: Option<u8>,
+        query: Option<String>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let from_stdin = path == STDIN_PATH;
+
+        // `sales.csv.gz` is picked a loader by `.csv`; the data itself is decoded on load.
+        let (inner_path, _) = strip_compression_suffix(path);
+        let extension = match format {
+            Some(format) => format.to_string(),
+            // Standard input has no extension; sniffing delimited text is the best guess.
+            None if from_stdin => "csv".to_string(),
+            None => Path::new(inner_path)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or("")
+                .to_string(),
+        }
+        .to_lowercase();
+
+        let mut source = Source {
+            path: path.to_string(),
+            extension,
+            delimiter,
+            query,
+            source_path: path.to_string(),
+            _spooled_file: None,
+        };
+        if source.query.is_some() && !source.is_database() {
+            return Err("'--sql' is only supported for SQLite databases".into());
+        }
+
+        if source.is_database() || source.is_workbook() {
+            let (input, compression) = decompress(open_input(path)?, path)?;
+            if from_stdin || compression.is_some() {
+                let file = spool_to_temp_file(input, &source.extension)?;
+                source.source_path = file.path().to_string_lossy().into_owned();
+                source._spooled_file = Some(file);
+            }
+        }
+        Ok(source)
+    }
+
+    pub fn is_database(&self) -> bool {
+        DATABASE_EXTENSIONS.contains(&self.extension.as_str())
+    }
+
+    pub fn is_workbook(&self) -> bool {
+        WORKBOOK_EXTENSIONS.contains(&self.extension.as_str())
+    }
+
+    /// The name sheets are titled with.
+    pub fn display_name(&self) -> String {
+        if self.path == STDIN_PATH {
+            "<stdin>".to_string()
+        } else {
+            self.path.clone()
+        }
+    }
+
+    /// The loader for the data: a query, an explicit delimiter, or else the extension.
+    pub fn loader(&self) -> Result<Box<dyn DataLoader>, Box<dyn Error>> {
+        if let Some(query) = &self.query {
+            Ok(Box::new(SqliteLoader::with_query(query.clone())))
+        } else if let Some(d) = self.delimiter {
+            Ok(Box::new(DelimitedLoader::with_delimiter(d)))
+        } else {
+            get_loader(&self.extension)
+        }
+    }
+
+    /// How a plain delimited file is written, for saving edits back to it.
+    /// Standard input, compressed files and other formats have none.
+    pub fn dialect(&self) -> Option<Dialect> {
+        if self.path == STDIN_PATH {
+            return None;
+        }
+        let loader = match self.delimiter {
+            Some(d) => DelimitedLoader::with_delimiter(d),
+            None => delimited_loader(&self.extension)?,
+        };
+        let (input, compression) = decompress(open_input(&self.path).ok()?, &self.path).ok()?;
+        if compression.is_some() {
+            return None;
+        }
+        loader.sniff(input).ok()
+    }
+
+    /// Reads the whole table at once. A database without a query gives its
+    /// list of tables.
+    pub fn load(&self) -> Result<TableData, Box<dyn Error>> {
+        if self.is_database() && self.query.is_none() {
+            return SqliteLoader::table_list(&self.source_path);
+        }
+        let loader = self.loader()?;
+        if self.is_database() || self.is_workbook() {
+            loader.load(&self.source_path)
+        } else {
+            let (input, _) = decompress(open_input(&self.path)?, &self.path)?;
+            loader.load_reader(input)
+        }
+    }
+
+    /// The first sheet to show: the table loading in the background, or the
+    /// tables of a database or the sheets of a workbook to pick from.
+    pub fn open_sheet(&self) -> Result<TuiApp, Box<dyn Error>> {
+        let display_name = self.display_name();
+
+        if self.is_database() && self.query.is_none() {
+            let table = VirtualTable::new(SqliteLoader::table_list(&self.source_path)?);
+            return Ok(TuiApp::new(table)
+                .with_kind(SheetKind::Database {
+                    path: self.source_path.clone(),
+                })
+                .with_title(display_name));
+        }
+
+        // Workbooks with several sheets open on a sheet list instead of the first sheet.
+        if self.is_workbook() {
+            let sheets = SpreadsheetLoader::sheet_names(&self.source_path)?;
+            if sheets.len() > 1 {
+                let table = VirtualTable::new(sheet_list_table(sheets));
+                return Ok(TuiApp::new(table)
+                    .with_kind(SheetKind::Workbook {
+                        path: self.source_path.clone(),
+                    })
+                    .with_title(display_name));
+            }
+        }
+
+        let loader = self.loader()?;
+        let load = if self.is_database() || self.is_workbook() {
+            // Opened by path, so there is no byte stream to measure progress on.
+            let source_path = self.source_path.clone();
+            BackgroundLoad::spawn(None, move |_, on_chunk| {
+                on_chunk(loader.load(&source_path)?);
+                Ok(())
+            })
+        } else {
+            let input = open_input(&self.path)?;
+            let total_bytes = if self.path == STDIN_PATH {
+                None
+            } else {
+                std::fs::metadata(&self.path).ok().map(|m| m.len())
+            };
+            let path = self.path.clone();
+            BackgroundLoad::spawn(total_bytes, move |bytes_read, on_chunk| {
+                let input = Box::new(CountingReader::new(input, bytes_read));
+                let (input, _) = decompress(input, &path)?;
+                loader.stream_reader(input, on_chunk)
+            })
+        };
+
+        let table = VirtualTable::new(TableData::new(Vec::new(), Vec::new()));
+        let mut app = TuiApp::new(table)
+            .with_title(display_name)
+            .with_background_load(load);
+        if let Some(dialect) = self.dialect() {
+            app = app.with_origin(self.path.clone(), dialect);
+        }
+        Ok(app)
+    }
+}
@@ -0,0 +1,106 @@
+// src/spreadsheet_loader.rs
+
+use std::error::Error;
+use std::io::{Cursor, Read, Seek};
+
+use calamine::{open_workbook_auto, open_workbook_auto_from_rs, Data, Range, Reader, Sheets};
+use chrono::Timelike;
+
+use crate::data_loader::{DataLoader, TableData};
+
+
+pub const WORKBOOK_EXTENSIONS: [&str; 5] = ["xlsx", "xlsm", "xlsb", "xls", "ods"];
+
+
+/// Loads worksheets from .xlsx, .xlsm, .xlsb, .xls and .ods workbooks.
+///
+/// Formula cells hold the value cached by the application that last saved
+/// the workbook; that is what gets displayed.
+pub struct SpreadsheetLoader;
+
+impl SpreadsheetLoader {
+    pub fn sheet_names(path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(open_workbook_auto(path)?.sheet_names())
+    }
+
+    pub fn load_sheet(path: &str, sheet: &str) -> Result<TableData, Box<dyn Error>> {
+        let range = open_workbook_auto(path)?.worksheet_range(sheet)?;
+        Ok(range_to_table(&range))
+    }
+}
+
+impl DataLoader for SpreadsheetLoader {
+    fn load(&self, path: &str) -> Result<TableData, Box<dyn Error>> {
+        first_sheet(open_workbook_auto(path)?)
+    }
+
+    /// Workbooks are zip or OLE containers that need seeking, so a stream is buffered whole.
+    fn load_reader(&self, mut reader: Box<dyn Read>) -> Result<TableData, Box<dyn Error>> {
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        first_sheet(open_workbook_auto_from_rs(Cursor::new(buffer))?)
+    }
+}
+
+fn first_sheet<RS: Read + Seek>(mut workbook: Sheets<RS>) -> Result<TableData, Box<dyn Error>> {
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or("Workbook contains no sheets")??;
+    Ok(range_to_table(&range))
+}
+
+
+/// Builds the screen listing a workbook's sheets, one row per sheet.
+pub fn sheet_list_table(sheets: Vec<String>) -> TableData {
+    TableData::new(vec!["Sheet".to_string()], vec![sheets])
+}
+
+/// The first row of the used range becomes the header.
+fn range_to_table(range: &Range<Data>) -> TableData {
+    let mut rows = range.rows();
+
+    let mut headers: Vec<String> = match rows.next() {
+        Some(row) => row.iter().map(render_cell).collect(),
+        None => Vec::new(),
+    };
+    for (i, header) in headers.iter_mut().enumerate() {
+        if header.is_empty() {
+            *header = format!("column_{}", i + 1);
+        }
+    }
+
+    let mut columns: Vec<Vec<String>> = headers
+        .iter()
+        .map(|_| Vec::with_capacity(range.height().saturating_sub(1)))
+        .collect();
+    for row in rows {
+        for (column, cell) in columns.iter_mut().zip(row) {
+            column.push(render_cell(cell));
+        }
+    }
+
+    TableData::new(headers, columns)
+}
+
+fn render_cell(cell: &Data) -> String {
+    match cell {
+        Data::Empty => String::new(),
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+        Data::Float(f) => f.to_string(),
+        Data::Int(i) => i.to_string(),
+        Data::Bool(b) => b.to_string(),
+        Data::Error(e) => e.to_string(),
+        Data::DateTime(dt) if dt.is_duration() => match dt.as_duration() {
+            Some(d) => {
+                let secs = d.num_seconds();
+                format!("{}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+            }
+            None => dt.as_f64().to_string(),
+        },
+        Data::DateTime(dt) => match dt.as_datetime() {
+            Some(d) if d.num_seconds_from_midnight() == 0 => d.format("%Y-%m-%d").to_string(),
+            Some(d) => d.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => dt.as_f64().to_string(),
+        },
+    }
+}
@@ -0,0 +1,1813 @@
+// src/sql.rs
+//
+// SELECT queries run in-process over open tables:
+//
+//     SELECT country, sum(amount) AS total FROM t WHERE year = 2024
+//         GROUP BY country ORDER BY 2 DESC LIMIT 10
+//     SELECT o.id, c.name FROM t o LEFT JOIN 'customers.csv' c ON o.customer = c.id
+//
+// Tables are named by the caller, and quoted names are file paths. Queries
+// support expressions and AS in the select list, DISTINCT, [LEFT] JOIN ... ON,
+// WHERE, GROUP BY and HAVING with the aggregation functions (count, sum,
+// avg, median, p90, ...), ORDER BY an expression, alias or position, LIMIT
+// and OFFSET. Values compare as in filters: numbers as numbers, dates in
+// time order, and null or empty cells never equal anything.
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use chrono::NaiveDateTime;
+
+use crate::aggregation::{
+    aggregate_column, format_number, AggregationFunction, AggregationResult, UnparseablePolicy,
+};
+use crate::column_type::{parse_boolean, parse_datetime, parse_float, TypedColumn};
+use crate::data_loader::TableData;
+use crate::virtual_table::VirtualTable;
+
+
+/// A table named in a query: an identifier such as `t`, or a quoted file path.
+pub struct TableName<'a> {
+    pub name: &'a str,
+    pub quoted: bool,
+}
+
+/// Runs a query, looking up the tables it names with `resolve`.
+///
+/// Plain selections of columns from one table come back as a view of that
+/// table; anything computed is a new table.
+pub fn run_query<F>(query: &str, mut resolve: F) -> Result<VirtualTable, Box<dyn Error>>
+where
+    F: FnMut(TableName) -> Result<VirtualTable, Box<dyn Error>>,
+{
+    let mut parser = Parser {
+        tokens: tokenize(query)?,
+        position: 0,
+    };
+    let select = parser.select()?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("Unexpected {} in query", token.describe()).into());
+    }
+
+    let mut tables = Vec::new();
+    let mut aliases = Vec::new();
+    for table in std::iter::once(&select.from).chain(select.joins.iter().map(|j| &j.table)) {
+        tables.push(resolve(TableName {
+            name: &table.name,
+            quoted: table.quoted,
+        })?);
+        aliases.push(table.alias());
+    }
+    Query::bind(select, &tables, &aliases)?.run(&tables)
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// A name, and whether it was quoted, which keeps it from being a keyword.
+    Ident(String, bool),
+    Str(String),
+    Number(String),
+    Symbol(&'static str),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Ident(name, _) => format!("'{}'", name),
+            Token::Str(s) => format!("'{}'", s),
+            Token::Number(n) => n.clone(),
+            Token::Symbol(s) => format!("'{}'", s),
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, Token::Ident(word, false) if word.eq_ignore_ascii_case(keyword))
+    }
+}
+
+/// Words that end an expression or a table name rather than alias it.
+const RESERVED: [&str; 23] = [
+    "select", "distinct", "from", "where", "group", "by", "having", "order", "limit", "offset",
+    "join", "left", "inner", "outer", "on", "as", "and", "or", "not", "asc", "desc", "is", "in",
+];
+
+const SYMBOLS: [&str; 18] = [
+    "<=", ">=", "<>", "!=", "==", "||", "(", ")", ",", ".", "*", "+", "-", "/", "%", "=", "<", ">",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    // Reads up to `end`, which is written twice to stand for itself.
+    let quoted = |i: &mut usize, end: char| -> Option<String> {
+        let mut value = String::new();
+        *i += 1;
+        while *i < chars.len() {
+            if chars[*i] == end {
+                if chars.get(*i + 1) == Some(&end) {
+                    value.push(end);
+                    *i += 2;
+                    continue;
+                }
+                *i += 1;
+                return Some(value);
+            }
+            value.push(chars[*i]);
+            *i += 1;
+        }
+        None
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '\'' => tokens.push(Token::Str(quoted(&mut i, '\'').ok_or("Unterminated string")?)),
+            '"' | '`' => {
+                let name = quoted(&mut i, c).ok_or("Unterminated quoted name")?;
+                tokens.push(Token::Ident(name, true));
+            }
+            _ if c.is_ascii_digit()
+                || (c == '.' && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit())) =>
+            {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                    i += 1;
+                }
+                tokens.push(Token::Number(chars[start..i].iter().collect()));
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect(), false));
+            }
+            _ => {
+                let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+                let symbol = SYMBOLS
+                    .iter()
+                    .find(|s| rest.starts_with(**s))
+                    .ok_or_else(|| format!("Unexpected '{}' in query", c))?;
+                tokens.push(Token::Symbol(symbol));
+                i += symbol.chars().count();
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+
+struct TableRef {
+    name: String,
+    quoted: bool,
+    alias: Option<String>,
+}
+
+impl TableRef {
+    /// What columns of the table are qualified with: the alias, or else the
+    /// name, or for a path, the file name without its extensions.
+    fn alias(&self) -> String {
+        match &self.alias {
+            Some(alias) => alias.clone(),
+            None if self.quoted => {
+                let file = self.name.rsplit(['/', '\\']).next().unwrap_or(&self.name);
+                file.split('.').next().unwrap_or(file).to_string()
+            }
+            None => self.name.clone(),
+        }
+    }
+}
+
+struct Join {
+    table: TableRef,
+    left: bool,
+    on: Expr,
+}
+
+enum SelectItem {
+    /// `*`, or `alias.*` for the columns of one table.
+    Wildcard(Option<String>),
+    Expr(Expr, Option<String>),
+}
+
+struct Select {
+    distinct: bool,
+    items: Vec<SelectItem>,
+    from: TableRef,
+    joins: Vec<Join>,
+    filter: Option<Expr>,
+    group_by: Vec<Expr>,
+    having: Option<Expr>,
+    order_by: Vec<(Expr, bool)>,
+    limit: Option<usize>,
+    offset: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Concat,
+}
+
+impl BinaryOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Or => "or",
+            BinaryOp::And => "and",
+            BinaryOp::Eq => "=",
+            BinaryOp::Ne => "<>",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Concat => "||",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ScalarFunction {
+    Lower,
+    Upper,
+    Length,
+    Abs,
+    Round,
+    Coalesce,
+}
+
+impl ScalarFunction {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "lower" => Some(ScalarFunction::Lower),
+            "upper" => Some(ScalarFunction::Upper),
+            "length" => Some(ScalarFunction::Length),
+            "abs" => Some(ScalarFunction::Abs),
+            "round" => Some(ScalarFunction::Round),
+            "coalesce" => Some(ScalarFunction::Coalesce),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Literal(Literal),
+    /// A column as written, before it is looked up.
+    Name(Option<String>, String),
+    /// A column by table and data column, once looked up.
+    Column(usize, usize),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    IsNull(Box<Expr>, bool),
+    In(Box<Expr>, Vec<Expr>, bool),
+    Like(Box<Expr>, Box<Expr>, bool),
+    /// An aggregation over the rows of a group; no argument is `count(*)`.
+    /// The name and DISTINCT are kept as written for the column header.
+    Aggregate {
+        function: AggregationFunction,
+        arg: Option<Box<Expr>>,
+        name: String,
+        distinct: bool,
+    },
+    Function(ScalarFunction, Vec<Expr>),
+}
+
+#[derive(Clone, Debug)]
+enum Literal {
+    Null,
+    Number(f64, String),
+    Bool(bool),
+    Text(String),
+}
+
+/// Aggregation functions as SQL names them, besides their own names.
+fn aggregation_function(name: &str) -> Option<AggregationFunction> {
+    let name = name.to_lowercase().replace('_', "");
+    match name.as_str() {
+        "avg" => Some(AggregationFunction::Mean),
+        "stdev" | "std" => Some(AggregationFunction::StdDev),
+        "var" => Some(AggregationFunction::Variance),
+        _ => AggregationFunction::parse(&name),
+    }
+}
+
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let matched = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        if matched {
+            self.position += 1;
+        }
+        matched
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let matched = self.peek().is_some_and(|t| t.is_keyword(keyword));
+        if matched {
+            self.position += 1;
+        }
+        matched
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), Box<dyn Error>> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.expected(&format!("'{}'", symbol)))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), Box<dyn Error>> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.expected(&keyword.to_uppercase()))
+        }
+    }
+
+    fn expected(&self, what: &str) -> Box<dyn Error> {
+        match self.peek() {
+            Some(token) => format!("Expected {}, found {}", what, token.describe()).into(),
+            None => format!("Expected {} at the end of the query", what).into(),
+        }
+    }
+
+    /// An alias after AS, or a bare name that is not a keyword.
+    fn alias(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        let explicit = self.eat_keyword("as");
+        match self.peek() {
+            Some(Token::Ident(name, quoted))
+                if *quoted || !RESERVED.iter().any(|word| name.eq_ignore_ascii_case(word)) =>
+            {
+                let name = name.clone();
+                self.position += 1;
+                Ok(Some(name))
+            }
+            _ if explicit => Err(self.expected("a name after AS")),
+            _ => Ok(None),
+        }
+    }
+
+    fn count(&mut self, what: &str) -> Result<usize, Box<dyn Error>> {
+        match self.next() {
+            Some(Token::Number(n)) => n
+                .parse()
+                .map_err(|_| format!("{} must be a whole number, not {}", what, n).into()),
+            _ => {
+                self.position -= 1;
+                Err(self.expected(&format!("a number after {}", what)))
+            }
+        }
+    }
+
+    fn select(&mut self) -> Result<Select, Box<dyn Error>> {
+        self.expect_keyword("select")?;
+        let distinct = self.eat_keyword("distinct");
+
+        let mut items = Vec::new();
+        loop {
+            items.push(self.select_item()?);
+            if !self.eat_symbol(",") {
+                break;
+            }
+        }
+
+        self.expect_keyword("from")?;
+        let from = self.table()?;
+        let mut joins = Vec::new();
+        loop {
+            let left = self.eat_keyword("left");
+            if left {
+                self.eat_keyword("outer");
+            } else {
+                self.eat_keyword("inner");
+            }
+            if !self.eat_keyword("join") {
+                if left {
+                    return Err(self.expected("JOIN"));
+                }
+                break;
+            }
+            let table = self.table()?;
+            self.expect_keyword("on")?;
+            let on = self.expr()?;
+            joins.push(Join { table, left, on });
+        }
+
+        let filter = if self.eat_keyword("where") {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        let mut group_by = Vec::new();
+        if self.eat_keyword("group") {
+            self.expect_keyword("by")?;
+            loop {
+                group_by.push(self.expr()?);
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+        let having = if self.eat_keyword("having") {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        let mut order_by = Vec::new();
+        if self.eat_keyword("order") {
+            self.expect_keyword("by")?;
+            loop {
+                let expr = self.expr()?;
+                let ascending = !self.eat_keyword("desc");
+                if ascending {
+                    self.eat_keyword("asc");
+                }
+                order_by.push((expr, ascending));
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+
+        let limit = if self.eat_keyword("limit") {
+            Some(self.count("LIMIT")?)
+        } else {
+            None
+        };
+        let offset = if self.eat_keyword("offset") {
+            self.count("OFFSET")?
+        } else {
+            0
+        };
+
+        Ok(Select {
+            distinct,
+            items,
+            from,
+            joins,
+            filter,
+            group_by,
+            having,
+            order_by,
+            limit,
+            offset,
+        })
+    }
+
+    fn select_item(&mut self) -> Result<SelectItem, Box<dyn Error>> {
+        if self.eat_symbol("*") {
+            return Ok(SelectItem::Wildcard(None));
+        }
+        // `alias.*`
+        if let (Some(Token::Ident(name, _)), Some(Token::Symbol(".")), Some(Token::Symbol("*"))) = (
+            self.tokens.get(self.position),
+            self.tokens.get(self.position + 1),
+            self.tokens.get(self.position + 2),
+        ) {
+            let name = name.clone();
+            self.position += 3;
+            return Ok(SelectItem::Wildcard(Some(name)));
+        }
+        let expr = self.expr()?;
+        Ok(SelectItem::Expr(expr, self.alias()?))
+    }
+
+    fn table(&mut self) -> Result<TableRef, Box<dyn Error>> {
+        let (name, quoted) = match self.next() {
+            Some(Token::Ident(name, quoted)) => (name, quoted),
+            Some(Token::Str(path)) => (path, true),
+            _ => {
+                self.position -= 1;
+                return Err(self.expected("a table"));
+            }
+        };
+        Ok(TableRef {
+            name,
+            quoted,
+            alias: self.alias()?,
+        })
+    }
+
+    fn expr(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let mut expr = self.and()?;
+        while self.eat_keyword("or") {
+            expr = Expr::Binary(BinaryOp::Or, Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let mut expr = self.not()?;
+        while self.eat_keyword("and") {
+            expr = Expr::Binary(BinaryOp::And, Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, Box<dyn Error>> {
+        if self.eat_keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let left = self.sum()?;
+
+        if self.eat_keyword("is") {
+            let negated = self.eat_keyword("not");
+            self.expect_keyword("null")?;
+            return Ok(Expr::IsNull(Box::new(left), !negated));
+        }
+
+        let negated = self.eat_keyword("not");
+        if self.eat_keyword("like") {
+            return Ok(Expr::Like(Box::new(left), Box::new(self.sum()?), negated));
+        }
+        if self.eat_keyword("in") {
+            self.expect_symbol("(")?;
+            let mut list = Vec::new();
+            loop {
+                list.push(self.expr()?);
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+            self.expect_symbol(")")?;
+            return Ok(Expr::In(Box::new(left), list, negated));
+        }
+        if negated {
+            return Err(self.expected("LIKE or IN after NOT"));
+        }
+
+        let op = match self.peek() {
+            Some(Token::Symbol("=" | "==")) => BinaryOp::Eq,
+            Some(Token::Symbol("<>" | "!=")) => BinaryOp::Ne,
+            Some(Token::Symbol("<")) => BinaryOp::Lt,
+            Some(Token::Symbol("<=")) => BinaryOp::Le,
+            Some(Token::Symbol(">")) => BinaryOp::Gt,
+            Some(Token::Symbol(">=")) => BinaryOp::Ge,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        Ok(Expr::Binary(op, Box::new(left), Box::new(self.sum()?)))
+    }
+
+    fn sum(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let mut expr = self.product()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol("+")) => BinaryOp::Add,
+                Some(Token::Symbol("-")) => BinaryOp::Sub,
+                Some(Token::Symbol("||")) => BinaryOp::Concat,
+                _ => return Ok(expr),
+            };
+            self.position += 1;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let mut expr = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol("*")) => BinaryOp::Mul,
+                Some(Token::Symbol("/")) => BinaryOp::Div,
+                Some(Token::Symbol("%")) => BinaryOp::Rem,
+                _ => return Ok(expr),
+            };
+            self.position += 1;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, Box<dyn Error>> {
+        if self.eat_symbol("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat_symbol("+") {
+            return self.unary();
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let token = self.next();
+        match token {
+            Some(Token::Symbol("(")) => {
+                let expr = self.expr()?;
+                self.expect_symbol(")")?;
+                Ok(expr)
+            }
+            Some(Token::Number(text)) => match parse_float(&text) {
+                Some(n) => Ok(Expr::Literal(Literal::Number(n, text))),
+                None => Err(format!("Invalid number {}", text).into()),
+            },
+            Some(Token::Str(text)) => Ok(Expr::Literal(Literal::Text(text))),
+            Some(ref t) if t.is_keyword("null") => Ok(Expr::Literal(Literal::Null)),
+            Some(ref t) if t.is_keyword("true") => Ok(Expr::Literal(Literal::Bool(true))),
+            Some(ref t) if t.is_keyword("false") => Ok(Expr::Literal(Literal::Bool(false))),
+            Some(Token::Ident(name, quoted)) => {
+                if !quoted && self.eat_symbol("(") {
+                    return self.call(&name);
+                }
+                if self.eat_symbol(".") {
+                    match self.next() {
+                        Some(Token::Ident(column, _)) => Ok(Expr::Name(Some(name), column)),
+                        _ => {
+                            self.position -= 1;
+                            Err(self.expected(&format!("a column after '{}.'", name)))
+                        }
+                    }
+                } else {
+                    Ok(Expr::Name(None, name))
+                }
+            }
+            _ => {
+                self.position -= 1;
+                Err(self.expected("a value or column"))
+            }
+        }
+    }
+
+    /// A function call, after its opening parenthesis.
+    fn call(&mut self, name: &str) -> Result<Expr, Box<dyn Error>> {
+        if let Some(function) = aggregation_function(name) {
+            if function == AggregationFunction::Count && self.eat_symbol("*") {
+                self.expect_symbol(")")?;
+                return Ok(Expr::Aggregate {
+                    function: AggregationFunction::Count,
+                    arg: None,
+                    name: name.to_string(),
+                    distinct: false,
+                });
+            }
+            let distinct = self.eat_keyword("distinct");
+            let arg = self.expr()?;
+            self.expect_symbol(")")?;
+            // As in SQL, count(x) leaves out nulls and count(distinct x) counts values.
+            let function = match (function, distinct) {
+                (AggregationFunction::Count, false) => AggregationFunction::NonNullCount,
+                (AggregationFunction::Count, true) => AggregationFunction::UniqueCount,
+                (function, false) => function,
+                (_, true) => return Err("DISTINCT is only supported in count()".into()),
+            };
+            return Ok(Expr::Aggregate {
+                function,
+                arg: Some(Box::new(arg)),
+                name: name.to_string(),
+                distinct,
+            });
+        }
+
+        let function =
+            ScalarFunction::parse(name).ok_or_else(|| format!("Unknown function '{}'", name))?;
+        let mut args = Vec::new();
+        if !self.eat_symbol(")") {
+            loop {
+                args.push(self.expr()?);
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+            self.expect_symbol(")")?;
+        }
+        let arity_ok = match function {
+            ScalarFunction::Round => matches!(args.len(), 1 | 2),
+            ScalarFunction::Coalesce => !args.is_empty(),
+            _ => args.len() == 1,
+        };
+        if !arity_ok {
+            return Err(format!("Wrong number of arguments to {}()", name.to_lowercase()).into());
+        }
+        Ok(Expr::Function(function, args))
+    }
+}
+
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Literal(Literal::Null) => write!(f, "null"),
+            Expr::Literal(Literal::Number(_, text)) => write!(f, "{}", text),
+            Expr::Literal(Literal::Bool(b)) => write!(f, "{}", b),
+            Expr::Literal(Literal::Text(text)) => write!(f, "'{}'", text.replace('\'', "''")),
+            Expr::Name(Some(table), name) => write!(f, "{}.{}", table, name),
+            Expr::Name(None, name) => write!(f, "{}", name),
+            Expr::Column(table, col) => write!(f, "#{}.{}", table, col),
+            Expr::Neg(a) => write!(f, "-{}", a),
+            Expr::Not(a) => write!(f, "not {}", a),
+            Expr::Binary(op, a, b) => write!(f, "{} {} {}", a, op.symbol(), b),
+            Expr::IsNull(a, true) => write!(f, "{} is null", a),
+            Expr::IsNull(a, false) => write!(f, "{} is not null", a),
+            Expr::In(a, list, negated) => {
+                let list: Vec<String> = list.iter().map(Expr::to_string).collect();
+                let not = if *negated { "not " } else { "" };
+                write!(f, "{} {}in ({})", a, not, list.join(", "))
+            }
+            Expr::Like(a, b, negated) => {
+                write!(f, "{} {}like {}", a, if *negated { "not " } else { "" }, b)
+            }
+            Expr::Aggregate { arg: None, name, .. } => write!(f, "{}(*)", name),
+            Expr::Aggregate { arg: Some(arg), name, distinct, .. } => {
+                write!(f, "{}({}{})", name, if *distinct { "distinct " } else { "" }, arg)
+            }
+            Expr::Function(function, args) => {
+                let args: Vec<String> = args.iter().map(Expr::to_string).collect();
+                let name = format!("{:?}", function).to_lowercase();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+        }
+    }
+}
+
+impl Expr {
+    fn contains_aggregate(&self) -> bool {
+        match self {
+            Expr::Aggregate { .. } => true,
+            Expr::Literal(_) | Expr::Name(..) | Expr::Column(..) => false,
+            Expr::Neg(a) | Expr::Not(a) | Expr::IsNull(a, _) => a.contains_aggregate(),
+            Expr::Binary(_, a, b) | Expr::Like(a, b, _) => {
+                a.contains_aggregate() || b.contains_aggregate()
+            }
+            Expr::In(a, list, _) => {
+                a.contains_aggregate() || list.iter().any(Expr::contains_aggregate)
+            }
+            Expr::Function(_, args) => args.iter().any(Expr::contains_aggregate),
+        }
+    }
+
+    /// The tables the expression reads columns of.
+    fn tables(&self, out: &mut Vec<usize>) {
+        match self {
+            Expr::Column(table, _) => out.push(*table),
+            Expr::Literal(_) | Expr::Name(..) => {}
+            Expr::Neg(a) | Expr::Not(a) | Expr::IsNull(a, _) => a.tables(out),
+            Expr::Binary(_, a, b) | Expr::Like(a, b, _) => {
+                a.tables(out);
+                b.tables(out);
+            }
+            Expr::In(a, list, _) => {
+                a.tables(out);
+                list.iter().for_each(|e| e.tables(out));
+            }
+            Expr::Aggregate { arg, .. } => {
+                if let Some(arg) = arg {
+                    arg.tables(out);
+                }
+            }
+            Expr::Function(_, args) => args.iter().for_each(|e| e.tables(out)),
+        }
+    }
+
+    /// Replaces column names with the table and data column they refer to.
+    fn bind(self, tables: &[VirtualTable], aliases: &[String]) -> Result<Expr, Box<dyn Error>> {
+        let bind = |e: Box<Expr>| e.bind(tables, aliases).map(Box::new);
+        let bind_all = |list: Vec<Expr>| {
+            list.into_iter()
+                .map(|e| e.bind(tables, aliases))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(match self {
+            Expr::Name(qualifier, name) => {
+                let (table, col) = find_column(tables, aliases, qualifier.as_deref(), &name)?;
+                Expr::Column(table, col)
+            }
+            Expr::Neg(a) => Expr::Neg(bind(a)?),
+            Expr::Not(a) => Expr::Not(bind(a)?),
+            Expr::IsNull(a, is_null) => Expr::IsNull(bind(a)?, is_null),
+            Expr::Binary(op, a, b) => Expr::Binary(op, bind(a)?, bind(b)?),
+            Expr::Like(a, b, negated) => Expr::Like(bind(a)?, bind(b)?, negated),
+            Expr::In(a, list, negated) => Expr::In(bind(a)?, bind_all(list)?, negated),
+            Expr::Aggregate { function, arg, name, distinct } => {
+                let arg = arg.map(bind).transpose()?;
+                if arg.as_ref().is_some_and(|arg| arg.contains_aggregate()) {
+                    return Err("Aggregations cannot be nested".into());
+                }
+                Expr::Aggregate { function, arg, name, distinct }
+            }
+            Expr::Function(function, args) => Expr::Function(function, bind_all(args)?),
+            other @ (Expr::Literal(_) | Expr::Column(..)) => other,
+        })
+    }
+}
+
+/// Looks a column up by name, in the table with the given alias or in
+/// whichever table has it, falling back to a case-insensitive match.
+fn find_column(
+    tables: &[VirtualTable],
+    aliases: &[String],
+    qualifier: Option<&str>,
+    name: &str,
+) -> Result<(usize, usize), Box<dyn Error>> {
+    let candidates: Vec<usize> = match qualifier {
+        Some(qualifier) => {
+            let table = aliases
+                .iter()
+                .position(|alias| alias == qualifier)
+                .or_else(|| aliases.iter().position(|a| a.eq_ignore_ascii_case(qualifier)))
+                .ok_or_else(|| format!("Unknown table '{}'", qualifier))?;
+            vec![table]
+        }
+        None => (0..tables.len()).collect(),
+    };
+
+    for exact in [true, false] {
+        let found: Vec<(usize, usize)> = candidates
+            .iter()
+            .filter_map(|&table| {
+                let headers = &tables[table].data.headers;
+                let col = (0..tables[table].num_columns())
+                    .map(|col| tables[table].data_column(col))
+                    .find(|&col| {
+                        if exact {
+                            headers[col] == name
+                        } else {
+                            headers[col].eq_ignore_ascii_case(name)
+                        }
+                    })?;
+                Some((table, col))
+            })
+            .collect();
+        match found.as_slice() {
+            [] => continue,
+            [one] => return Ok(*one),
+            _ => return Err(format!("Column '{}' is ambiguous, qualify it with a table", name).into()),
+        }
+    }
+    Err(format!("Unknown column '{}'", name).into())
+}
+
+
+/// A value while a query runs. Text borrows from the tables when it can.
+#[derive(Clone, Debug)]
+enum Value<'a> {
+    Null,
+    Number(f64),
+    Bool(bool),
+    DateTime(NaiveDateTime),
+    Text(Cow<'a, str>),
+}
+
+impl Value<'_> {
+    fn text(&self) -> Option<Cow<'_, str>> {
+        match self {
+            Value::Null => None,
+            Value::Number(n) => Some(Cow::Owned(format_number(*n))),
+            Value::Bool(b) => Some(Cow::Borrowed(if *b { "true" } else { "false" })),
+            Value::DateTime(dt) => Some(Cow::Owned(if dt.time() == chrono::NaiveTime::MIN {
+                dt.format("%Y-%m-%d").to_string()
+            } else {
+                dt.format("%Y-%m-%d %H:%M:%S").to_string()
+            })),
+            Value::Text(text) => Some(Cow::Borrowed(text)),
+        }
+    }
+
+    fn number(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            Value::Text(text) => parse_float(text.trim()),
+            Value::Null | Value::DateTime(_) => None,
+        }
+    }
+
+    /// The truth of a value, or none for null.
+    fn logic(&self) -> Option<bool> {
+        match self {
+            Value::Null => None,
+            value => Some(value.truthy()),
+        }
+    }
+
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            Value::Number(n) => *n != 0.0,
+            Value::Text(text) => parse_boolean(text.trim()).unwrap_or(false),
+            Value::Null | Value::DateTime(_) => false,
+        }
+    }
+
+    /// What equal values have in common, for grouping and joining.
+    fn key(&self) -> Option<String> {
+        self.text().map(Cow::into_owned)
+    }
+}
+
+/// Orders two values, or `None` when either is null.
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    let ordering = match (a, b) {
+        (Value::Null, _) | (_, Value::Null) => return None,
+        (Value::Number(x), Value::Number(y)) => x.total_cmp(y),
+        (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+        (Value::DateTime(x), Value::DateTime(y)) => x.cmp(y),
+        (Value::DateTime(x), Value::Text(y)) => match parse_datetime(y.trim()) {
+            Some(y) => x.cmp(&y),
+            None => compare_text(&a.text()?, y),
+        },
+        (Value::Text(x), Value::DateTime(y)) => match parse_datetime(x.trim()) {
+            Some(x) => x.cmp(y),
+            None => compare_text(x, &b.text()?),
+        },
+        (Value::Bool(x), Value::Text(y)) => match parse_boolean(y.trim()) {
+            Some(y) => x.cmp(&y),
+            None => compare_text(&a.text()?, y),
+        },
+        (Value::Text(x), Value::Bool(y)) => match parse_boolean(x.trim()) {
+            Some(x) => x.cmp(y),
+            None => compare_text(x, &b.text()?),
+        },
+        _ => compare_text(&a.text()?, &b.text()?),
+    };
+    Some(ordering)
+}
+
+/// Numbers when both sides read as one, text otherwise.
+fn compare_text(a: &str, b: &str) -> Ordering {
+    match (parse_float(a.trim()), parse_float(b.trim())) {
+        (Some(x), Some(y)) => x.total_cmp(&y),
+        _ => a.cmp(b),
+    }
+}
+
+/// Orders rows for ORDER BY, with nulls first as in sorted sheets.
+fn sort_order(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        _ => compare(a, b).unwrap_or(Ordering::Equal),
+    }
+}
+
+/// SQL LIKE: `%` is any run of characters and `_` any one, ignoring case.
+fn like(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+    let (mut t, mut p) = (0, 0);
+    // Where the last % was, and the text position it has been matched up to.
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('%') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '_' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '%')
+}
+
+
+/// The rows of a join: for each, the data row of every table, or none for
+/// tables a left join found nothing in.
+struct Rows {
+    width: usize,
+    slots: Vec<Option<usize>>,
+}
+
+impl Rows {
+    fn len(&self) -> usize {
+        self.slots.len() / self.width
+    }
+
+    fn get(&self, index: usize) -> &[Option<usize>] {
+        &self.slots[index * self.width..(index + 1) * self.width]
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &[Option<usize>]> {
+        self.slots.chunks(self.width)
+    }
+}
+
+/// Where an expression is evaluated: on a row, or on a group of rows, where
+/// aggregations are over the group and columns come from its first row.
+#[derive(Clone, Copy)]
+enum Scope<'r> {
+    Row(&'r [Option<usize>]),
+    Group(&'r [usize]),
+}
+
+struct Query {
+    distinct: bool,
+    /// Output columns and their names.
+    columns: Vec<(Expr, String)>,
+    joins: Vec<(bool, Expr)>,
+    filter: Option<Expr>,
+    group_by: Vec<Expr>,
+    grouped: bool,
+    having: Option<Expr>,
+    order_by: Vec<(Expr, bool)>,
+    limit: Option<usize>,
+    offset: usize,
+}
+
+impl Query {
+    fn bind(
+        select: Select,
+        tables: &[VirtualTable],
+        aliases: &[String],
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut columns = Vec::new();
+        for item in select.items {
+            match item {
+                SelectItem::Wildcard(qualifier) => {
+                    let table_indices: Vec<usize> = match &qualifier {
+                        Some(qualifier) => vec![aliases
+                            .iter()
+                            .position(|alias| alias.eq_ignore_ascii_case(qualifier))
+                            .ok_or_else(|| format!("Unknown table '{}'", qualifier))?],
+                        None => (0..tables.len()).collect(),
+                    };
+                    for table in table_indices {
+                        for col in 0..tables[table].num_columns() {
+                            let data_col = tables[table].data_column(col);
+                            let name = tables[table].header(col).to_string();
+                            columns.push((Expr::Column(table, data_col), name));
+                        }
+                    }
+                }
+                SelectItem::Expr(expr, alias) => {
+                    let name = alias.unwrap_or_else(|| match &expr {
+                        Expr::Name(_, name) => name.clone(),
+                        other => other.to_string(),
+                    });
+                    columns.push((expr.bind(tables, aliases)?, name));
+                }
+            }
+        }
+
+        let mut joins = Vec::new();
+        for join in select.joins {
+            let on = join.on.bind(tables, aliases)?;
+            if on.contains_aggregate() {
+                return Err("Aggregations are not allowed in JOIN conditions".into());
+            }
+            joins.push((join.left, on));
+        }
+        let filter = select.filter.map(|e| e.bind(tables, aliases)).transpose()?;
+        if filter.as_ref().is_some_and(Expr::contains_aggregate) {
+            return Err("Aggregations are not allowed in WHERE, use HAVING".into());
+        }
+        let group_by = select
+            .group_by
+            .into_iter()
+            .map(|e| Self::bind_output_ref(e, &columns, tables, aliases))
+            .collect::<Result<Vec<_>, _>>()?;
+        if group_by.iter().any(Expr::contains_aggregate) {
+            return Err("Aggregations are not allowed in GROUP BY".into());
+        }
+        let having = select
+            .having
+            .map(|e| Self::bind_output_ref(e, &columns, tables, aliases))
+            .transpose()?;
+        let order_by = select
+            .order_by
+            .into_iter()
+            .map(|(e, ascending)| {
+                Ok((Self::bind_output_ref(e, &columns, tables, aliases)?, ascending))
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+
+        let grouped = !group_by.is_empty()
+            || having.is_some()
+            || columns.iter().any(|(e, _)| e.contains_aggregate())
+            || order_by.iter().any(|(e, _)| e.contains_aggregate());
+
+        Ok(Query {
+            distinct: select.distinct,
+            columns,
+            joins,
+            filter,
+            group_by,
+            grouped,
+            having,
+            order_by,
+            limit: select.limit,
+            offset: select.offset,
+        })
+    }
+
+    /// Binds an expression that may also name an output column, by its
+    /// position or its alias.
+    fn bind_output_ref(
+        expr: Expr,
+        columns: &[(Expr, String)],
+        tables: &[VirtualTable],
+        aliases: &[String],
+    ) -> Result<Expr, Box<dyn Error>> {
+        match &expr {
+            Expr::Literal(Literal::Number(n, text)) => {
+                let position = *n as usize;
+                if n.fract() != 0.0 || position == 0 || position > columns.len() {
+                    return Err(format!("There is no output column {}", text).into());
+                }
+                Ok(columns[position - 1].0.clone())
+            }
+            Expr::Name(None, name) => {
+                let own = find_column(tables, aliases, None, name);
+                match columns.iter().find(|(_, alias)| alias == name) {
+                    // A column of the tables wins over an alias that only renames it.
+                    Some((bound, _)) if own.is_err() => Ok(bound.clone()),
+                    _ => expr.bind(tables, aliases),
+                }
+            }
+            _ => expr.bind(tables, aliases),
+        }
+    }
+
+    fn run(self, tables: &[VirtualTable]) -> Result<VirtualTable, Box<dyn Error>> {
+        let mut engine = Engine {
+            tables,
+            rows: Rows {
+                width: tables.len(),
+                slots: Vec::new(),
+            },
+        };
+        engine.rows.slots = tables[0]
+            .rows()
+            .iter()
+            .flat_map(|&row| {
+                std::iter::once(Some(row)).chain(std::iter::repeat_n(None, tables.len() - 1))
+            })
+            .collect();
+        for (index, (left, on)) in self.joins.iter().enumerate() {
+            engine.rows = engine.join(index + 1, on, *left);
+        }
+
+        let units: Vec<usize> = (0..engine.rows.len())
+            .filter(|&i| {
+                self.filter
+                    .as_ref()
+                    .is_none_or(|f| engine.eval(f, Scope::Row(engine.rows.get(i))).truthy())
+            })
+            .collect();
+
+        if let Some(view) = self.as_view(&engine, &units) {
+            return Ok(view);
+        }
+
+        let groups = if self.grouped {
+            let mut groups = engine.group(&self.group_by, &units);
+            if let Some(having) = &self.having {
+                groups.retain(|group| engine.eval(having, Scope::Group(group)).truthy());
+            }
+            groups
+        } else {
+            Vec::new()
+        };
+        let scope = |unit: usize| {
+            if self.grouped {
+                Scope::Group(&groups[unit])
+            } else {
+                Scope::Row(engine.rows.get(units[unit]))
+            }
+        };
+        let count = if self.grouped { groups.len() } else { units.len() };
+        let mut order: Vec<usize> = (0..count).collect();
+
+        if !self.order_by.is_empty() {
+            let keys: Vec<Vec<Value>> = order
+                .iter()
+                .map(|&unit| {
+                    self.order_by
+                        .iter()
+                        .map(|(expr, _)| engine.eval(expr, scope(unit)))
+                        .collect()
+                })
+                .collect();
+            order.sort_by(|&a, &b| {
+                self.order_by
+                    .iter()
+                    .enumerate()
+                    .map(|(k, (_, ascending))| {
+                        let ordering = sort_order(&keys[a][k], &keys[b][k]);
+                        if *ascending {
+                            ordering
+                        } else {
+                            ordering.reverse()
+                        }
+                    })
+                    .find(|o| o.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+
+        let mut output: Vec<Vec<Option<String>>> = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        let limit = self.limit.unwrap_or(usize::MAX);
+        for unit in order {
+            if output.len() >= limit.saturating_add(self.offset) {
+                break;
+            }
+            let row: Vec<Option<String>> = self
+                .columns
+                .iter()
+                .map(|(expr, _)| engine.output(expr, scope(unit)))
+                .collect();
+            if self.distinct && !seen.insert(row.clone()) {
+                continue;
+            }
+            output.push(row);
+        }
+
+        let headers = self.columns.iter().map(|(_, name)| name.clone()).collect();
+        let rows: Vec<_> = output.into_iter().skip(self.offset).collect();
+        let mut cells = vec![Vec::with_capacity(rows.len()); self.columns.len()];
+        let mut nulls = vec![Vec::with_capacity(rows.len()); self.columns.len()];
+        for row in rows {
+            for (col, value) in row.into_iter().enumerate() {
+                nulls[col].push(value.is_none());
+                cells[col].push(value.unwrap_or_default());
+            }
+        }
+        Ok(VirtualTable::new(TableData::with_nulls(headers, cells, nulls)))
+    }
+
+    /// The result as a view of the queried table, when it only picks,
+    /// filters and orders columns and rows of a single table.
+    fn as_view(&self, engine: &Engine, units: &[usize]) -> Option<VirtualTable> {
+        let table = &engine.tables[0];
+        if engine.tables.len() > 1 || self.grouped || self.distinct {
+            return None;
+        }
+        let mut columns = Vec::new();
+        for (expr, name) in &self.columns {
+            match expr {
+                Expr::Column(0, col) if table.data.headers[*col] == *name => columns.push(*col),
+                _ => return None,
+            }
+        }
+
+        let mut rows: Vec<usize> = units
+            .iter()
+            .filter_map(|&unit| engine.rows.get(unit)[0])
+            .collect();
+        if !self.order_by.is_empty() {
+            let keys: Vec<Vec<Value>> = rows
+                .iter()
+                .map(|&row| {
+                    self.order_by
+                        .iter()
+                        .map(|(expr, _)| engine.eval(expr, Scope::Row(&[Some(row)])))
+                        .collect()
+                })
+                .collect();
+            let mut order: Vec<usize> = (0..rows.len()).collect();
+            order.sort_by(|&a, &b| {
+                self.order_by
+                    .iter()
+                    .enumerate()
+                    .map(|(k, (_, ascending))| {
+                        let ordering = sort_order(&keys[a][k], &keys[b][k]);
+                        if *ascending {
+                            ordering
+                        } else {
+                            ordering.reverse()
+                        }
+                    })
+                    .find(|o| o.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+            rows = order.into_iter().map(|i| rows[i]).collect();
+        }
+        let rows = rows
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+        let mut view = VirtualTable::with_rows(Arc::clone(&table.data), Some(rows));
+        view.set_columns(columns);
+        Some(view)
+    }
+}
+
+
+struct Engine<'a> {
+    tables: &'a [VirtualTable],
+    rows: Rows,
+}
+
+impl<'a> Engine<'a> {
+    fn cell(&self, table: usize, col: usize, row: Option<usize>) -> Value<'a> {
+        let Some(row) = row else {
+            return Value::Null;
+        };
+        let data: &'a TableData = &self.tables[table].data;
+        let cell = &data.columns[col][row];
+        if data.is_null(col, row) || cell.trim().is_empty() {
+            return Value::Null;
+        }
+        let value = match &data.typed[col] {
+            TypedColumn::Integer(v) => v[row].map(|n| Value::Number(n as f64)),
+            TypedColumn::Float(v) => v[row].map(Value::Number),
+            TypedColumn::Boolean(v) => v[row].map(Value::Bool),
+            TypedColumn::Date(v) => v[row]
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(Value::DateTime),
+            TypedColumn::DateTime(v) => v[row].map(Value::DateTime),
+            TypedColumn::String => None,
+        };
+        value.unwrap_or(Value::Text(Cow::Borrowed(cell)))
+    }
+
+    fn eval<'s>(&'s self, expr: &'s Expr, scope: Scope) -> Value<'s> {
+        match expr {
+            Expr::Literal(Literal::Null) => Value::Null,
+            Expr::Literal(Literal::Number(n, _)) => Value::Number(*n),
+            Expr::Literal(Literal::Bool(b)) => Value::Bool(*b),
+            Expr::Literal(Literal::Text(text)) => Value::Text(Cow::Borrowed(text)),
+            Expr::Name(..) => Value::Null,
+            Expr::Column(table, col) => {
+                let row = match scope {
+                    Scope::Row(row) => row[*table],
+                    Scope::Group(group) => group.first().and_then(|&i| self.rows.get(i)[*table]),
+                };
+                self.cell(*table, *col, row)
+            }
+            Expr::Neg(a) => match self.eval(a, scope).number() {
+                Some(n) => Value::Number(-n),
+                None => Value::Null,
+            },
+            Expr::Not(a) => match self.eval(a, scope) {
+                Value::Null => Value::Null,
+                value => Value::Bool(!value.truthy()),
+            },
+            Expr::IsNull(a, is_null) => {
+                Value::Bool(matches!(self.eval(a, scope), Value::Null) == *is_null)
+            }
+            Expr::Like(a, pattern, negated) => {
+                let value = self.eval(a, scope);
+                let pattern = self.eval(pattern, scope);
+                match (value.text(), pattern.text()) {
+                    (Some(text), Some(pattern)) => Value::Bool(like(&text, &pattern) != *negated),
+                    _ => Value::Null,
+                }
+            }
+            Expr::In(a, list, negated) => {
+                let value = self.eval(a, scope);
+                if let Value::Null = value {
+                    return Value::Null;
+                }
+                let found = list
+                    .iter()
+                    .any(|e| compare(&value, &self.eval(e, scope)).is_some_and(Ordering::is_eq));
+                Value::Bool(found != *negated)
+            }
+            Expr::Binary(op, a, b) => self.binary(*op, a, b, scope),
+            Expr::Aggregate { function, arg, .. } => match scope {
+                Scope::Group(group) => self.aggregate(*function, arg.as_deref(), group),
+                Scope::Row(_) => Value::Null,
+            },
+            Expr::Function(function, args) => self.function(*function, args, scope),
+        }
+    }
+
+    fn binary<'s>(&'s self, op: BinaryOp, a: &'s Expr, b: &'s Expr, scope: Scope) -> Value<'s> {
+        // Three-valued logic: null unless the other side settles the result.
+        if let BinaryOp::And | BinaryOp::Or = op {
+            let settles = op == BinaryOp::Or;
+            let x = self.eval(a, scope).logic();
+            if x == Some(settles) {
+                return Value::Bool(settles);
+            }
+            return match (x, self.eval(b, scope).logic()) {
+                (_, Some(y)) if y == settles => Value::Bool(settles),
+                (Some(_), Some(_)) => Value::Bool(!settles),
+                _ => Value::Null,
+            };
+        }
+        let (x, y) = (self.eval(a, scope), self.eval(b, scope));
+        let accepts = |check: fn(Ordering) -> bool| match compare(&x, &y) {
+            Some(ordering) => Value::Bool(check(ordering)),
+            None => Value::Null,
+        };
+        let arithmetic = |f: fn(f64, f64) -> Option<f64>| match (x.number(), y.number()) {
+            (Some(x), Some(y)) => f(x, y).map_or(Value::Null, Value::Number),
+            _ => Value::Null,
+        };
+        match op {
+            BinaryOp::Eq => accepts(Ordering::is_eq),
+            BinaryOp::Ne => accepts(Ordering::is_ne),
+            BinaryOp::Lt => accepts(Ordering::is_lt),
+            BinaryOp::Le => accepts(Ordering::is_le),
+            BinaryOp::Gt => accepts(Ordering::is_gt),
+            BinaryOp::Ge => accepts(Ordering::is_ge),
+            BinaryOp::Add => arithmetic(|x, y| Some(x + y)),
+            BinaryOp::Sub => arithmetic(|x, y| Some(x - y)),
+            BinaryOp::Mul => arithmetic(|x, y| Some(x * y)),
+            // Dividing by zero gives null, as in most databases.
+            BinaryOp::Div => arithmetic(|x, y| (y != 0.0).then(|| x / y)),
+            BinaryOp::Rem => arithmetic(|x, y| (y != 0.0).then(|| x % y)),
+            BinaryOp::Concat => match (x.text(), y.text()) {
+                (Some(x), Some(y)) => Value::Text(Cow::Owned(format!("{}{}", x, y))),
+                _ => Value::Null,
+            },
+            BinaryOp::And | BinaryOp::Or => unreachable!("handled above"),
+        }
+    }
+
+    fn function<'s>(&'s self, function: ScalarFunction, args: &'s [Expr], scope: Scope) -> Value<'s> {
+        let first = self.eval(&args[0], scope);
+        match function {
+            ScalarFunction::Lower => first
+                .text()
+                .map_or(Value::Null, |t| Value::Text(Cow::Owned(t.to_lowercase()))),
+            ScalarFunction::Upper => first
+                .text()
+                .map_or(Value::Null, |t| Value::Text(Cow::Owned(t.to_uppercase()))),
+            ScalarFunction::Length => first
+                .text()
+                .map_or(Value::Null, |t| Value::Number(t.chars().count() as f64)),
+            ScalarFunction::Abs => first.number().map_or(Value::Null, |n| Value::Number(n.abs())),
+            ScalarFunction::Round => {
+                let digits = match args.get(1) {
+                    Some(arg) => self.eval(arg, scope).number().unwrap_or(0.0),
+                    None => 0.0,
+                };
+                let scale = 10f64.powi(digits as i32);
+                first
+                    .number()
+                    .map_or(Value::Null, |n| Value::Number((n * scale).round() / scale))
+            }
+            ScalarFunction::Coalesce => std::iter::once(first)
+                .chain(args[1..].iter().map(|arg| self.eval(arg, scope)))
+                .find(|value| !matches!(value, Value::Null))
+                .unwrap_or(Value::Null),
+        }
+    }
+
+    /// Computes an aggregation over a group with the same engine as the
+    /// aggregation panel. Plain columns are aggregated in place; other
+    /// arguments are evaluated into a column first. The minimum, maximum and
+    /// mode are cells and keep their text, so that `min(code)` gives "007";
+    /// comparisons and arithmetic still read them as numbers.
+    fn aggregate(
+        &self,
+        function: AggregationFunction,
+        arg: Option<&Expr>,
+        group: &[usize],
+    ) -> Value<'static> {
+        let Some(arg) = arg else {
+            return Value::Number(group.len() as f64);
+        };
+
+        let results = match arg {
+            Expr::Column(table, col) if group.iter().all(|&i| self.rows.get(i)[*table].is_some()) => {
+                let rows = group.iter().filter_map(|&i| self.rows.get(i)[*table]);
+                aggregate_column(
+                    &self.tables[*table].data,
+                    *col,
+                    rows,
+                    &[function],
+                    UnparseablePolicy::Skip,
+                )
+            }
+            _ => {
+                let values: Vec<Option<String>> = group
+                    .iter()
+                    .map(|&i| {
+                        let value = self.eval(arg, Scope::Row(self.rows.get(i)));
+                        value.text().map(Cow::into_owned)
+                    })
+                    .collect();
+                let nulls = values.iter().map(Option::is_none).collect();
+                let cells = values.into_iter().map(Option::unwrap_or_default).collect();
+                let data =
+                    TableData::with_nulls(vec!["value".to_string()], vec![cells], vec![nulls]);
+                aggregate_column(
+                    &data,
+                    0,
+                    0..group.len(),
+                    &[function],
+                    UnparseablePolicy::Skip,
+                )
+            }
+        };
+        let numeric = !matches!(
+            function,
+            AggregationFunction::Min | AggregationFunction::Max | AggregationFunction::Mode
+        );
+        match results.get(&function) {
+            Some(AggregationResult::Value {
+                value: Some(value), ..
+            }) => match parse_float(value).filter(|_| numeric) {
+                Some(n) => Value::Number(n),
+                None => Value::Text(Cow::Owned(value.clone())),
+            },
+            _ => Value::Null,
+        }
+    }
+
+    /// The text of an output cell. Columns keep the text of their cells.
+    fn output(&self, expr: &Expr, scope: Scope) -> Option<String> {
+        if let Expr::Column(table, col) = expr {
+            let row = match scope {
+                Scope::Row(row) => row[*table],
+                Scope::Group(group) => group.first().and_then(|&i| self.rows.get(i)[*table]),
+            }?;
+            let data = &self.tables[*table].data;
+            return (!data.is_null(*col, row)).then(|| data.columns[*col][row].clone());
+        }
+        self.eval(expr, scope).text().map(Cow::into_owned)
+    }
+
+    /// Splits rows into groups by the key expressions, in order of first
+    /// appearance. Without keys, all rows form one group, even none.
+    fn group(&self, keys: &[Expr], units: &[usize]) -> Vec<Vec<usize>> {
+        if keys.is_empty() {
+            return vec![units.to_vec()];
+        }
+        let mut index: HashMap<Vec<Option<String>>, usize> = HashMap::new();
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for &unit in units {
+            let row = self.rows.get(unit);
+            let key = keys
+                .iter()
+                .map(|expr| self.eval(expr, Scope::Row(row)).key())
+                .collect();
+            let group = *index.entry(key).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[group].push(unit);
+        }
+        groups
+    }
+
+    /// Joins the rows so far with the table at `table`. Equality between a
+    /// column of the new table and the tables before it is done with a hash
+    /// lookup; other conditions are checked on every pair of rows.
+    fn join(&self, table: usize, on: &Expr, left: bool) -> Rows {
+        let mut slots = Vec::new();
+        let candidates: Vec<usize> = self.tables[table].rows().to_vec();
+        let mut combined = vec![None; self.rows.width];
+        let mut emit = |row: &[Option<usize>], other: Option<usize>, slots: &mut Vec<_>| {
+            combined.copy_from_slice(row);
+            combined[table] = other;
+            slots.extend_from_slice(&combined);
+        };
+
+        let equi = match on {
+            Expr::Binary(BinaryOp::Eq, a, b) => {
+                let side = |e: &Expr| {
+                    let mut used = Vec::new();
+                    e.tables(&mut used);
+                    (!used.is_empty()).then(|| used.iter().all(|&t| t == table))
+                };
+                match (side(a), side(b)) {
+                    (Some(false), Some(true)) => Some((a, b)),
+                    (Some(true), Some(false)) => Some((b, a)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        match equi {
+            Some((outer, inner)) => {
+                let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+                let mut probe = vec![None; self.rows.width];
+                for &row in &candidates {
+                    probe[table] = Some(row);
+                    if let Some(key) = self.eval(inner, Scope::Row(&probe)).key() {
+                        index.entry(key).or_default().push(row);
+                    }
+                }
+                for row in self.rows.iter() {
+                    let matches = self
+                        .eval(outer, Scope::Row(row))
+                        .key()
+                        .and_then(|key| index.get(&key));
+                    match matches {
+                        Some(matches) => {
+                            for &other in matches {
+                                emit(row, Some(other), &mut slots);
+                            }
+                        }
+                        None if left => emit(row, None, &mut slots),
+                        None => {}
+                    }
+                }
+            }
+            None => {
+                let mut probe = vec![None; self.rows.width];
+                for row in self.rows.iter() {
+                    probe.copy_from_slice(row);
+                    let mut matched = false;
+                    for &other in &candidates {
+                        probe[table] = Some(other);
+                        if self.eval(on, Scope::Row(&probe)).truthy() {
+                            matched = true;
+                            emit(row, Some(other), &mut slots);
+                        }
+                    }
+                    if !matched && left {
+                        emit(row, None, &mut slots);
+                    }
+                }
+            }
+        }
+        Rows {
+            width: self.rows.width,
+            slots,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(headers: &[&str], rows: &[&[&str]]) -> TableData {
+        let columns = (0..headers.len())
+            .map(|col| rows.iter().map(|row| row[col].to_string()).collect())
+            .collect();
+        TableData::new(headers.iter().map(|h| h.to_string()).collect(), columns)
+    }
+
+    fn view(text: &str) -> VirtualTable {
+        let sales = Arc::new(table(
+            &["id", "country", "amount"],
+            &[
+                &["1", "FR", "10"],
+                &["2", "DE", "20"],
+                &["3", "FR", "30"],
+                &["4", "US", "5"],
+                &["5", "DE", "25"],
+            ],
+        ));
+        let countries = Arc::new(table(
+            &["code", "label", "dial"],
+            &[&["FR", "France", "033"], &["DE", "Germany", "049"]],
+        ));
+        run_query(text, |table| match table.name {
+            "sales" => Ok(VirtualTable::with_rows(Arc::clone(&sales), None)),
+            "countries" => Ok(VirtualTable::with_rows(Arc::clone(&countries), None)),
+            name => Err(format!("No table {}", name).into()),
+        })
+        .unwrap()
+    }
+
+    fn query(text: &str) -> Vec<Vec<String>> {
+        let view = view(text);
+        (0..view.num_rows())
+            .map(|row| {
+                (0..view.num_columns())
+                    .map(|col| {
+                        if view.is_null(col, row) {
+                            "null".to_string()
+                        } else {
+                            view.cell(col, row).to_string()
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn order_by_position() {
+        let rows = query("SELECT country, sum(amount) FROM sales GROUP BY country ORDER BY 2 DESC");
+        assert_eq!(rows, [["DE", "45"], ["FR", "40"], ["US", "5"]]);
+    }
+
+    #[test]
+    fn group_by_with_having() {
+        let rows = query(
+            "SELECT country, count(id) AS n FROM sales GROUP BY country \
+             HAVING count(id) > 1 ORDER BY country",
+        );
+        assert_eq!(rows, [["DE", "2"], ["FR", "2"]]);
+    }
+
+    #[test]
+    fn left_join_keeps_rows_without_a_match() {
+        let rows = query(
+            "SELECT s.id, c.label FROM sales s LEFT JOIN countries c ON s.country = c.code \
+             ORDER BY s.id",
+        );
+        assert_eq!(
+            rows,
+            [["1", "France"], ["2", "Germany"], ["3", "France"], ["4", "null"], ["5", "Germany"]]
+        );
+    }
+
+    #[test]
+    fn limit_and_offset_apply_after_distinct() {
+        let rows = query("SELECT DISTINCT country FROM sales ORDER BY country LIMIT 2 OFFSET 1");
+        assert_eq!(rows, [["FR"], ["US"]]);
+    }
+
+    #[test]
+    fn dividing_by_zero_gives_null() {
+        let rows = query("SELECT amount / 0, amount % 0, amount / 4 FROM sales WHERE id = 1");
+        assert_eq!(rows, [["null", "null", "2.5"]]);
+    }
+
+    #[test]
+    fn aggregate_headers_echo_the_query() {
+        let view = view(
+            "SELECT count(*), COUNT(id), count(DISTINCT country), avg(amount), std(amount), \
+             sum(amount) AS total FROM sales",
+        );
+        let headers: Vec<&str> = (0..view.num_columns()).map(|col| view.header(col)).collect();
+        assert_eq!(
+            headers,
+            [
+                "count(*)",
+                "COUNT(id)",
+                "count(distinct country)",
+                "avg(amount)",
+                "std(amount)",
+                "total"
+            ]
+        );
+    }
+
+    #[test]
+    fn and_or_use_three_valued_logic() {
+        let rows = query(
+            "SELECT null AND true, null AND false, null OR true, null OR false, \
+             NOT (null AND true), true AND 1 = 1 FROM sales WHERE id = 1",
+        );
+        assert_eq!(rows, [["null", "false", "true", "null", "null", "true"]]);
+        let rows = query("SELECT id FROM sales WHERE NOT (amount > 15 AND null) ORDER BY id");
+        assert_eq!(rows, [["1"], ["4"]]);
+    }
+
+    #[test]
+    fn extremes_keep_the_text_of_their_cells() {
+        let rows = query("SELECT min(dial), max(dial), count(dial) FROM countries");
+        assert_eq!(rows, [["033", "049", "2"]]);
+        let rows = query("SELECT max(amount), max(amount) + 1 FROM sales HAVING min(amount) < 10");
+        assert_eq!(rows, [["30", "31"]]);
+    }
+
+    #[test]
+    fn unknown_columns_are_rejected() {
+        let result = run_query("SELECT missing FROM t", |_| {
+            Ok(VirtualTable::new(table(&["id"], &[&["1"]])))
+        });
+        assert!(result.is_err());
+    }
+}
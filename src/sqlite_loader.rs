@@ -0,0 +1,121 @@
+// src/sqlite_loader.rs
+
+use std::error::Error;
+use std::io::Read;
+
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags};
+
+use crate::data_loader::{spool_to_temp_file, DataLoader, TableData};
+
+pub const DATABASE_EXTENSIONS: [&str; 3] = ["db", "sqlite", "sqlite3"];
+
+
+/// Loads the result of a query against a SQLite database.
+pub struct SqliteLoader {
+    pub query: String,
+}
+
+impl SqliteLoader {
+    pub fn with_query(query: impl Into<String>) -> Self {
+        SqliteLoader {
+            query: query.into(),
+        }
+    }
+
+    pub fn load_table(path: &str, table: &str) -> Result<TableData, Box<dyn Error>> {
+        let query = format!("SELECT * FROM {}", quote_identifier(table));
+        load_query(&open(path)?, &query)
+    }
+
+    /// Lists the tables and views of a database along with their row counts.
+    pub fn table_list(path: &str) -> Result<TableData, Box<dyn Error>> {
+        let conn = open(path)?;
+        let mut statement = conn.prepare(
+            "SELECT name, type FROM sqlite_master \
+             WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%' \
+             ORDER BY name",
+        )?;
+        let entries = statement
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut names = Vec::with_capacity(entries.len());
+        let mut types = Vec::with_capacity(entries.len());
+        let mut counts = Vec::with_capacity(entries.len());
+        for (name, kind) in entries {
+            let query = format!("SELECT count(*) FROM {}", quote_identifier(&name));
+            // A broken view should not keep the rest of the database from opening.
+            let count = conn
+                .query_row(&query, [], |row| row.get::<_, i64>(0))
+                .map(|n| n.to_string())
+                .unwrap_or_else(|_| "?".to_string());
+            names.push(name);
+            types.push(kind);
+            counts.push(count);
+        }
+
+        Ok(TableData::new(
+            vec!["Name".to_string(), "Type".to_string(), "Rows".to_string()],
+            vec![names, types, counts],
+        ))
+    }
+}
+
+impl DataLoader for SqliteLoader {
+    fn load(&self, path: &str) -> Result<TableData, Box<dyn Error>> {
+        load_query(&open(path)?, &self.query)
+    }
+
+    /// SQLite needs random access to a real file, so a stream is written out first.
+    fn load_reader(&self, reader: Box<dyn Read>) -> Result<TableData, Box<dyn Error>> {
+        let file = spool_to_temp_file(reader, "db")?;
+        self.load(&file.path().to_string_lossy())
+    }
+}
+
+
+fn open(path: &str) -> Result<Connection, Box<dyn Error>> {
+    let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    Ok(Connection::open_with_flags(path, flags)?)
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn load_query(conn: &Connection, query: &str) -> Result<TableData, Box<dyn Error>> {
+    let mut statement = conn.prepare(query)?;
+    let headers: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+    let mut columns: Vec<Vec<String>> = headers.iter().map(|_| Vec::new()).collect();
+    let mut nulls: Vec<Vec<bool>> = headers.iter().map(|_| Vec::new()).collect();
+
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        for col in 0..headers.len() {
+            let value = row.get_ref(col)?;
+            nulls[col].push(matches!(value, ValueRef::Null));
+            columns[col].push(render_value(value));
+        }
+    }
+
+    Ok(TableData::with_nulls(headers, columns, nulls))
+}
+
+fn render_value(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) => f.to_string(),
+        ValueRef::Text(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        ValueRef::Blob(bytes) => match std::str::from_utf8(bytes) {
+            Ok(s) => s.to_string(),
+            Err(_) => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+        },
+    }
+}
@@ -1,36 +1,176 @@
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
 use ratatui::{
     backend::Backend,
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
+    text::{Line, Span},
     widgets::{
-        Block, Borders, Cell, Clear, List, ListItem, Row, Table, TableState, ListState,
+        Block, Borders, Cell, Clear, Gauge, List, ListItem, Row, Table, TableState, ListState,
     },
-    Terminal,
+    Frame, Terminal,
 };
-use crossterm::event::{self, Event, KeyCode};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
 
+use crate::aggregation::{
+    aggregate_column, describe_table, results_table, AggregationFunction, AggregationResult, UnparseablePolicy,
+};
+use crate::background_loader::{format_bytes, BackgroundLoad, LoadMessage};
+use crate::column_type::ColumnType;
 use crate::virtual_table::VirtualTable;
-use crate::data_loader::TableData;
+use crate::data_loader::{Dialect, TableData};
+use crate::data_writer::{writer_for_path, DataWriter, DelimitedWriter};
+use crate::filter::Filter;
+use crate::group_by::{frequency_table, group_rows, group_table};
+use crate::prompt::{Prompt, PromptEvent};
+use crate::search::{Search, SearchOptions, SearchScope};
+use crate::sort::{Collation, SortKey};
+use crate::source::Source;
+use crate::spreadsheet_loader::SpreadsheetLoader;
+use crate::sql::{self, TableName};
+use crate::sqlite_loader::SqliteLoader;
 
 use strum::IntoEnumIterator;
-use strum_macros::EnumIter;
 
-fn compare_cells(a: &str, b: &str) -> std::cmp::Ordering {
-    
-    match (a.parse::<f64>(), b.parse::<f64>()) {
-        (Ok(a_num), Ok(b_num)) => a_num.partial_cmp(&b_num).unwrap_or(std::cmp::Ordering::Equal),
-        _ => a.cmp(b),
-    }
+/// Most rows looked at when sizing a column to its content.
+const WIDTH_SAMPLE_ROWS: usize = 1000;
+
+/// Placeholder drawn in place of null cells so they differ from empty strings.
+const NULL_DISPLAY: &str = "null";
+
+/// Most operations kept for undo.
+const HISTORY_LIMIT: usize = 100;
+
+/// Space ratatui puts between table columns, and the width of the row marker.
+const COLUMN_SPACING: u16 = 2;
+const HIGHLIGHT_SYMBOL: &str = "-> ";
+
+/// What a sheet shows, which decides what Enter does on one of its rows.
+pub enum SheetKind {
+    /// Plain data: Enter opens the row as a field/value detail sheet.
+    Table,
+    /// The sheet names of a workbook: Enter opens the selected sheet.
+    Workbook { path: String },
+    /// The tables and views of a SQLite database: Enter opens the selected one.
+    Database { path: String },
+    /// One row per group of `source`, whose rows are listed in `groups`; the
+    /// first `keys` columns hold the key values. Enter opens a group's rows.
+    /// Frequency sheets are the single-key case.
+    GroupBy {
+        source: Arc<TableData>,
+        groups: Vec<Vec<usize>>,
+        keys: usize,
+    },
+    /// One row per column of the sheet below, whose data columns are listed
+    /// in `columns`. Enter goes back to the column of a row.
+    Describe { columns: Vec<usize> },
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, EnumIter, PartialOrd, Ord)]
-pub enum AggregationFunction {
-    Count,
-    UniqueCount,
-    Sum,
-    
+/// What a sheet asks of whoever runs it, after a key press.
+pub enum SheetAction {
+    Continue,
+    /// Show a new sheet on top of this one.
+    Push(Box<TuiApp>),
+    /// Close this sheet and go back to the one below.
+    Close,
+    /// Close this sheet and select a data column in the one below.
+    SelectColumn(usize),
+}
+
+/// The search being typed, and where the cursor was when it started.
+struct SearchPrompt {
+    prompt: Prompt,
+    options: SearchOptions,
+    origin: (usize, usize),
+    error: Option<String>,
+}
+
+/// The filter being typed; `new_sheet` filters are pushed as a sheet of their own.
+struct FilterPrompt {
+    prompt: Prompt,
+    new_sheet: bool,
+    error: Option<String>,
+}
+
+/// The file name being typed for a save. `overwrite` is an existing file
+/// the user has been warned about, which the next Enter writes over.
+struct SavePrompt {
+    prompt: Prompt,
+    aggregations: bool,
+    overwrite: Option<String>,
+    error: Option<String>,
+}
+
+/// A new value being typed for a cell, by data column and row.
+struct EditPrompt {
+    prompt: Prompt,
+    cell: (usize, usize),
+}
+
+/// The delimited file a sheet was loaded from, which edits are saved back to
+/// in the dialect it was written in.
+pub struct Origin {
+    pub path: String,
+    pub dialect: Dialect,
+}
+
+/// A command being typed after `:`, such as `sql SELECT ...`.
+struct CommandPrompt {
+    prompt: Prompt,
+    error: Option<String>,
+}
+
+/// A filter applied in place, with the rows shown before it.
+pub struct AppliedFilter {
+    pub filter: Filter,
+    pub unfiltered_rows: Vec<usize>,
+}
+
+/// How a sheet is viewed, saved before each operation so that it can be
+/// undone. Row lists are shared between states while they are unchanged.
+struct ViewState {
+    rows: Arc<Vec<usize>>,
+    columns: Vec<usize>,
+    filter: Option<(Filter, Arc<Vec<usize>>)>,
+    sort_keys: Vec<SortKey>,
+    collation: Collation,
+    selected_aggregations: HashMap<usize, Vec<AggregationFunction>>,
+    unparseable_policy: UnparseablePolicy,
+    group_keys: Vec<usize>,
+    column_widths: Vec<ColumnWidth>,
+    pinned_columns: usize,
+    cursor: (usize, usize),
+    /// Whether the data differs from what was last saved.
+    unsaved_changes: bool,
+    /// The data rows and columns there were, short of the rest of a load.
+    loaded: (usize, usize),
+}
+
+/// An operation in the history, and the view on the other side of it: the
+/// one before it while it is applied, the one after it once undone.
+struct HistoryEntry {
+    description: String,
+    state: ViewState,
+    /// What the operation changed in the data, as it is on the other side of it.
+    change: Option<DataChange>,
+    /// Whether the operation changes what saving back writes.
+    edits: bool,
+}
+
+/// A change to the data itself, which the view state does not capture.
+enum DataChange {
+    Cell {
+        col: usize,
+        row: usize,
+        value: Option<String>,
+    },
+    /// A column's type, `None` for the inferred one.
+    ColumnType {
+        col: usize,
+        column_type: Option<ColumnType>,
+    },
 }
 
 #[derive(Clone, Copy)]
@@ -41,25 +181,78 @@ pub enum ColumnWidth {
 
 pub struct TuiApp {
     pub table: VirtualTable,
+    pub kind: SheetKind,
+    pub title: String,
+    pub status_message: Option<String>,
     pub selected_row: usize,
     pub selected_column: usize,
-    pub table_state: TableState,
+    /// First row drawn; only the rows that fit on screen are materialized.
+    pub row_offset: usize,
+    pub visible_rows: usize,
 
     pub show_aggregation_popup: bool,
     pub aggregation_state: ListState, 
+    /// Aggregations by data column.
     pub selected_aggregations: HashMap<usize, Vec<AggregationFunction>>, 
+    aggregation_cache: Option<HashMap<usize, HashMap<AggregationFunction, AggregationResult>>>,
+    pub unparseable_policy: UnparseablePolicy,
+    /// Data columns marked as group-by keys, in the order they were marked.
+    pub group_keys: Vec<usize>,
 
     pub awaiting_g_key: bool,
+    /// Widths by data column, so that they follow a column wherever it is shown.
     pub column_widths: Vec<ColumnWidth>,
+    /// Content widths by data column, computed on first use and dropped when the data changes.
+    content_widths: Vec<Option<u16>>,
     pub horizontal_offset: u16,
     pub table_area_width: u16,
+    /// How many leading columns stay in place when scrolling sideways.
+    pub pinned_columns: usize,
+
+    pub background_load: Option<BackgroundLoad>,
+    /// The data rows and columns there were when the background load
+    /// finished, which views recorded while it ran are brought up to.
+    load_end: (usize, usize),
+
+    /// The last search, whose hits are highlighted and visited with n and N.
+    pub search: Option<Search>,
+    search_prompt: Option<SearchPrompt>,
+
+    pub filter: Option<AppliedFilter>,
+    filter_prompt: Option<FilterPrompt>,
+
+    save_prompt: Option<SavePrompt>,
+
+    command_prompt: Option<CommandPrompt>,
+    /// Files queries have opened, by path and by alias, for later queries
+    /// on this sheet and the sheets opened from it.
+    pub open_tables: HashMap<String, Arc<TableData>>,
+
+    /// The sort in place, most significant key first.
+    pub sort_keys: Vec<SortKey>,
+    pub collation: Collation,
+
+    /// Operations to undo, the latest last, and undone ones to redo.
+    undo_stack: Vec<HistoryEntry>,
+    redo_stack: Vec<HistoryEntry>,
+
+    pub origin: Option<Origin>,
+    edit_prompt: Option<EditPrompt>,
+    /// Values edited cells had when last saved, by data column and row.
+    edited_cells: HashMap<(usize, usize), Option<String>>,
+    /// Data rows inserted since the last save.
+    inserted_rows: HashSet<usize>,
+    /// The data row each inserted row was added below, `None` for the top,
+    /// which is where saving back writes it.
+    inserted_below: HashMap<usize, Option<usize>>,
+    /// Whether there are edits, insertions or deletions not saved yet.
+    pub unsaved_changes: bool,
+    /// Set by a first q with unsaved changes; the next q quits anyway.
+    confirm_quit: bool,
 }
 
 impl TuiApp {
     pub fn new(table: VirtualTable) -> Self {
-        let mut table_state = TableState::default();
-        table_state.select(Some(0));
-
         let mut aggregation_state = ListState::default();
         aggregation_state.select(Some(0));
 
@@ -67,192 +260,490 @@ impl TuiApp {
 
         TuiApp {
             table,
+            kind: SheetKind::Table,
+            title: "Table".to_string(),
+            status_message: None,
             selected_row: 0,
             selected_column: 0,
-            table_state,
+            row_offset: 0,
+            visible_rows: 0,
 
             show_aggregation_popup: false,
             aggregation_state,
             selected_aggregations: HashMap::new(),
+            aggregation_cache: None,
+            unparseable_policy: UnparseablePolicy::default(),
+            group_keys: Vec::new(),
 
             awaiting_g_key: false,
             column_widths: vec![ColumnWidth::Fixed(15); headers_len],
+            content_widths: vec![None; headers_len],
 
             horizontal_offset: 0,
             table_area_width: 0,
+            pinned_columns: 0,
+
+            background_load: None,
+            load_end: (0, 0),
+
+            search: None,
+            search_prompt: None,
+
+            filter: None,
+            filter_prompt: None,
+
+            save_prompt: None,
+
+            command_prompt: None,
+            open_tables: HashMap::new(),
+
+            sort_keys: Vec::new(),
+            collation: Collation::default(),
+
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+
+            origin: None,
+            edit_prompt: None,
+            edited_cells: HashMap::new(),
+            inserted_rows: HashSet::new(),
+            inserted_below: HashMap::new(),
+            unsaved_changes: false,
+            confirm_quit: false,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: SheetKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Marks the sheet as loaded from a delimited file, which saving to the
+    /// same path rewrites in its dialect.
+    pub fn with_origin(mut self, path: impl Into<String>, dialect: Dialect) -> Self {
+        self.origin = Some(Origin {
+            path: path.into(),
+            dialect,
+        });
+        self
+    }
+
+    /// Fills the table from a load running in the background, so the rows
+    /// already read can be browsed while the rest streams in.
+    pub fn with_background_load(mut self, load: BackgroundLoad) -> Self {
+        self.background_load = Some(load);
+        self
+    }
+
+    /// Takes in the rows loaded in the background since the last call. Call
+    /// it before drawing when driving the sheet with `handle_key` and `render`.
+    pub fn poll_background_load(&mut self) {
+        let Some(load) = &self.background_load else {
+            return;
+        };
+        let messages = load.poll();
+        let cancelled = load.is_cancelled();
+
+        let mut finished = false;
+        let mut appended = false;
+        for message in messages {
+            match message {
+                LoadMessage::Chunk(chunk) => {
+                    let new_rows = self.table.append(chunk);
+                    // With a filter in place, only the matching rows of the chunk are shown.
+                    if let Some(applied) = &mut self.filter {
+                        let kept = applied
+                            .filter
+                            .matching_rows(&self.table.data, new_rows.clone());
+                        self.table.show_rows(kept);
+                        applied.unfiltered_rows.extend(new_rows);
+                    } else {
+                        self.table.show_rows(new_rows);
+                    }
+                    appended = true;
+                }
+                LoadMessage::Finished => finished = true,
+                LoadMessage::Failed(e) => {
+                    self.status_message = Some(e);
+                    finished = true;
+                }
+            }
+        }
+
+        if appended {
+            // New rows take their place in a sort made while loading; the sorted
+            // rows before them form one run, so this is close to a merge.
+            if !self.sort_keys.is_empty() {
+                self.table.sort_by(&self.sort_keys, self.collation);
+            }
+            self.column_widths
+                .resize(self.table.data.headers.len(), ColumnWidth::Fixed(15));
+            self.invalidate_caches();
+        }
+
+        if finished {
+            if cancelled && self.status_message.is_none() {
+                self.status_message = Some(format!(
+                    "Loading cancelled after {} rows",
+                    self.table.num_rows()
+                ));
+            }
+            self.background_load = None;
+            self.load_end = (self.table.data.num_rows(), self.table.data.headers.len());
         }
     }
 
     pub fn main_loop<B: Backend>(
         &mut self,
         terminal: &mut Terminal<B>,
-    ) -> Result<Option<TuiApp>, Box<dyn Error>> {
+    ) -> Result<SheetAction, Box<dyn Error>> {
         loop {
+            self.poll_background_load();
             self.draw_ui(terminal)?;
 
             if crossterm::event::poll(std::time::Duration::from_millis(100))? {
                 if let Event::Key(key) = event::read()? {
-                    if self.show_aggregation_popup {
-                        
-                        match key.code {
-                            KeyCode::Up => {
-                                let i = match self.aggregation_state.selected() {
-                                    Some(i) => {
-                                        if i == 0 {
-                                            AggregationFunction::iter().count() - 1
-                                        } else {
-                                            i - 1
-                                        }
-                                    }
-                                    None => 0,
-                                };
-                                self.aggregation_state.select(Some(i));
-                            }
-                            KeyCode::Down => {
-                                let i = match self.aggregation_state.selected() {
-                                    Some(i) => {
-                                        if i >= AggregationFunction::iter().count() - 1 {
-                                            0
-                                        } else {
-                                            i + 1
-                                        }
-                                    }
-                                    None => 0,
-                                };
-                                self.aggregation_state.select(Some(i));
-                            }
-                            KeyCode::Char(' ') => {
-                                
-                                let index = self.aggregation_state.selected().unwrap_or(0);
-                                let agg = AggregationFunction::iter().nth(index).unwrap();
-                                let entry = self
-                                    .selected_aggregations
-                                    .entry(self.selected_column)
-                                    .or_insert_with(Vec::new);
-                                if entry.contains(&agg) {
-                                    entry.retain(|&x| x != agg);
-                                    if entry.is_empty() {
-                                        self.selected_aggregations.remove(&self.selected_column);
-                                    }
-                                } else {
-                                    entry.push(agg);
-                                }
+                    match self.handle_key(key) {
+                        SheetAction::Continue => {}
+                        action => return Ok(action),
+                    }
+                } else {
+
+                    self.awaiting_g_key = false;
+                }
+            }
+        }
+    }
+
+    /// Handles a key press. Sheets the key opens or closes are left to the
+    /// caller, which keeps the stack of sheets.
+    pub fn handle_key(&mut self, key: KeyEvent) -> SheetAction {
+        if self.search_prompt.is_some() {
+            self.handle_search_key(key);
+        } else if self.filter_prompt.is_some() {
+            if let Some(new_app) = self.handle_filter_key(key) {
+                return SheetAction::Push(Box::new(new_app));
+            }
+        } else if self.save_prompt.is_some() {
+            self.handle_save_key(key);
+        } else if self.edit_prompt.is_some() {
+            self.handle_edit_key(key);
+        } else if self.command_prompt.is_some() {
+            if let Some(new_app) = self.handle_command_key(key) {
+                return SheetAction::Push(Box::new(new_app));
+            }
+        } else if self.show_aggregation_popup {
+            
+            match key.code {
+                KeyCode::Up => {
+                    let i = match self.aggregation_state.selected() {
+                        Some(i) => {
+                            if i == 0 {
+                                AggregationFunction::iter().count() - 1
+                            } else {
+                                i - 1
                             }
-                            KeyCode::Enter | KeyCode::Char('q') => {
-                                
-                                self.show_aggregation_popup = false;
+                        }
+                        None => 0,
+                    };
+                    self.aggregation_state.select(Some(i));
+                }
+                KeyCode::Down => {
+                    let i = match self.aggregation_state.selected() {
+                        Some(i) => {
+                            if i >= AggregationFunction::iter().count() - 1 {
+                                0
+                            } else {
+                                i + 1
                             }
-                            _ => {}
+                        }
+                        None => 0,
+                    };
+                    self.aggregation_state.select(Some(i));
+                }
+                KeyCode::Char(' ') => {
+                    
+                    let index = self.aggregation_state.selected().unwrap_or(0);
+                    let agg = AggregationFunction::iter().nth(index).unwrap();
+                    let col = self.selected_data_column();
+                    let selected = self.selected_aggregations.get(&col).is_some_and(|a| a.contains(&agg));
+                    let header = &self.table.data.headers[col];
+                    self.record(if selected {
+                        format!("remove {:?} of {}", agg, header)
+                    } else {
+                        format!("add {:?} of {}", agg, header)
+                    });
+                    let entry = self.selected_aggregations.entry(col).or_default();
+                    if entry.contains(&agg) {
+                        entry.retain(|&x| x != agg);
+                        if entry.is_empty() {
+                            self.selected_aggregations.remove(&col);
                         }
                     } else {
+                        entry.push(agg);
+                    }
+                    self.aggregation_cache = None;
+                }
+                KeyCode::Char('s') => {
+                    self.record(self.unparseable_policy.toggle().label());
+                    self.unparseable_policy = self.unparseable_policy.toggle();
+                    self.aggregation_cache = None;
+                }
+                KeyCode::Enter | KeyCode::Char('q') => {
+                    
+                    self.show_aggregation_popup = false;
+                }
+                _ => {}
+            }
+        } else {
+            
+            if self.awaiting_g_key {
+                match key.code {
+                    KeyCode::Char('-') => {
+                        if !self.selected_aggregations.is_empty() {
+                            self.record("clear aggregations");
+                        }
+                        self.selected_aggregations.clear();
+                        self.aggregation_cache = None;
+                        self.awaiting_g_key = false;
+                    }
+                    KeyCode::Char('f') => {
+                        self.open_filter_prompt(true);
+                        self.awaiting_g_key = false;
+                    }
+                    KeyCode::Char('v') => {
+                        self.unhide_columns();
+                        self.awaiting_g_key = false;
+                    }
+                    KeyCode::Char('t') if self.table.num_columns() > 0 => {
+                        let col = self.selected_data_column();
+                        let header = &self.table.data.headers[col];
+                        self.record_type_change(format!("infer type of {}", header), col);
+                        self.table.data_mut().infer_column_type(col);
+                        self.invalidate_caches();
+                        self.awaiting_g_key = false;
+                    }
+                    KeyCode::Char('_') => {
+                        self.record("toggle all column widths");
+                        for width in &mut self.column_widths {
+                            *width = match *width {
+                                ColumnWidth::Fixed(_) => ColumnWidth::Content,
+                                ColumnWidth::Content => ColumnWidth::Fixed(15),
+                            };
+                        }
+                        self.awaiting_g_key = false;
+                    }
+                    _ => {
                         
-                        if self.awaiting_g_key {
-                            match key.code {
-                                KeyCode::Char('-') => {
-                                    
-                                    self.selected_aggregations.clear();
-                                    self.awaiting_g_key = false;
-                                }
-                                KeyCode::Char('_') => {
-                                    
-                                    for width in &mut self.column_widths {
-                                        *width = match *width {
-                                            ColumnWidth::Fixed(_) => ColumnWidth::Content,
-                                            ColumnWidth::Content => ColumnWidth::Fixed(15),
-                                        };
-                                    }
-                                    self.awaiting_g_key = false;
-                                }
-                                _ => {
-                                    
-                                    self.awaiting_g_key = false;
-                                }
-                            }
+                        self.awaiting_g_key = false;
+                    }
+                }
+            } else {
+                let confirming_quit = std::mem::take(&mut self.confirm_quit);
+                match key.code {
+                    KeyCode::Char('c')
+                        if key.modifiers.contains(KeyModifiers::CONTROL) =>
+                    {
+                        if let Some(load) = &self.background_load {
+                            load.cancel();
+                        }
+                    }
+                    KeyCode::Char('s')
+                        if key.modifiers.contains(KeyModifiers::CONTROL) =>
+                    {
+                        self.open_save_prompt();
+                    }
+                    KeyCode::Char('r')
+                        if key.modifiers.contains(KeyModifiers::CONTROL) =>
+                    {
+                        self.redo();
+                    }
+                    KeyCode::Char('u')
+                        if !key.modifiers.contains(KeyModifiers::CONTROL) =>
+                    {
+                        self.undo();
+                    }
+                    KeyCode::Char('e')
+                        if !key.modifiers.contains(KeyModifiers::CONTROL) =>
+                    {
+                        self.open_edit_prompt();
+                    }
+                    KeyCode::Char('a')
+                        if !key.modifiers.contains(KeyModifiers::CONTROL) =>
+                    {
+                        self.insert_row();
+                    }
+                    KeyCode::Char('d')
+                        if !key.modifiers.contains(KeyModifiers::CONTROL) =>
+                    {
+                        self.delete_row();
+                    }
+                    KeyCode::Char('U') => {
+                        return SheetAction::Push(Box::new(self.open_history()));
+                    }
+                    KeyCode::Char('g') => {
+                        
+                        self.awaiting_g_key = true;
+                    }
+                    KeyCode::Char('f') => self.open_filter_prompt(false),
+                    KeyCode::Char(':') => {
+                        self.command_prompt = Some(CommandPrompt {
+                            prompt: Prompt::new(":"),
+                            error: None,
+                        });
+                    }
+                    KeyCode::Char('/') if self.table.num_columns() > 0 => {
+                        self.open_search_prompt();
+                    }
+                    KeyCode::Char('n') => self.jump_to_match(true),
+                    KeyCode::Char('N') => self.jump_to_match(false),
+                    KeyCode::Char('_') if self.table.num_columns() > 0 => {
+                        let col = self.selected_data_column();
+                        self.record(format!("toggle width of {}", self.table.data.headers[col]));
+                        if let Some(width) = self.column_widths.get_mut(col) {
+                            *width = match *width {
+                                ColumnWidth::Fixed(_) => ColumnWidth::Content,
+                                ColumnWidth::Content => ColumnWidth::Fixed(15),
+                            };
+                        }
+                    }
+                    KeyCode::Char('t') if self.table.num_columns() > 0 => {
+                        self.cycle_column_type();
+                    }
+                    KeyCode::Char('!') if self.table.num_columns() > 0 => {
+                        let col = self.selected_data_column();
+                        let header = &self.table.data.headers[col];
+                        self.record(if self.group_keys.contains(&col) {
+                            format!("remove group key {}", header)
                         } else {
-                            match key.code {
-                                KeyCode::Char('g') => {
-                                    
-                                    self.awaiting_g_key = true;
-                                }
-                                KeyCode::Char('_') => {
-                                    
-                                    if let Some(width) = self.column_widths.get_mut(self.selected_column) {
-                                        *width = match *width {
-                                            ColumnWidth::Fixed(_) => ColumnWidth::Content,
-                                            ColumnWidth::Content => ColumnWidth::Fixed(15),
-                                        };
-                                    }
-                                }
-                                KeyCode::Up => {
-                                    if self.selected_row > 0 {
-                                        self.selected_row -= 1;
-                                    }
-                                    self.table_state.select(Some(self.selected_row));
-                                }
-                                KeyCode::Down => {
-                                    let num_rows = if self.table.data.columns.is_empty() {
-                                        0
-                                    } else {
-                                        self.table.data.columns[0].len()
-                                    };
-                                    if self.selected_row < num_rows - 1 {
-                                        self.selected_row += 1;
-                                    }
-                                    self.table_state.select(Some(self.selected_row));
-                                }
-                                KeyCode::Left => {
-                                    if self.selected_column > 0 {
-                                        self.selected_column -= 1;
-                                        self.adjust_horizontal_offset();
-                                    }
-                                }
-                                KeyCode::Right => {
-                                    if self.selected_column < self.table.data.headers.len() - 1 {
-                                        self.selected_column += 1;
-                                        self.adjust_horizontal_offset();
-                                    }
-                                }
-                                KeyCode::Char('[') => {
-                                    self.sort_table(true); 
-                                }
-                                KeyCode::Char(']') => {
-                                    self.sort_table(false); 
-                                }
-                                KeyCode::Char(' ') => {
-                                    self.show_aggregation_popup = true;
-                                    self.aggregation_state.select(Some(0));
-                                }
-                                KeyCode::Enter => {
-                                    let new_app = self.open_detail_view();
-                                    return Ok(Some(new_app));
-                                }
-                                KeyCode::Char('q') => {
-                                    return Ok(None);
-                                }
-                                _ => {}
-                            }
+                            format!("group by {}", header)
+                        });
+                        if self.group_keys.contains(&col) {
+                            self.group_keys.retain(|&key| key != col);
+                        } else {
+                            self.group_keys.push(col);
                         }
                     }
-                } else {
-
-                    self.awaiting_g_key = false;
+                    KeyCode::Char('F') if self.table.num_columns() > 0 => {
+                        return SheetAction::Push(Box::new(self.open_frequency()));
+                    }
+                    KeyCode::Char('I') => {
+                        return SheetAction::Push(Box::new(self.open_describe()));
+                    }
+                    KeyCode::Char('G') => {
+                        self.status_message = None;
+                        match self.open_group_by() {
+                            Ok(new_app) => return SheetAction::Push(Box::new(new_app)),
+                            Err(e) => self.status_message = Some(e.to_string()),
+                        }
+                    }
+                    KeyCode::Up => {
+                        self.selected_row = self.selected_row.saturating_sub(1);
+                    }
+                    KeyCode::Down => {
+                        let last_row = self.table.num_rows().saturating_sub(1);
+                        self.selected_row = (self.selected_row + 1).min(last_row);
+                    }
+                    KeyCode::PageUp => {
+                        self.selected_row =
+                            self.selected_row.saturating_sub(self.visible_rows.max(1));
+                    }
+                    KeyCode::PageDown => {
+                        let last_row = self.table.num_rows().saturating_sub(1);
+                        self.selected_row =
+                            (self.selected_row + self.visible_rows.max(1)).min(last_row);
+                    }
+                    KeyCode::Home => {
+                        self.selected_row = 0;
+                    }
+                    KeyCode::End => {
+                        self.selected_row = self.table.num_rows().saturating_sub(1);
+                    }
+                    KeyCode::Left if self.selected_column > 0 => {
+                        self.selected_column -= 1;
+                        self.adjust_horizontal_offset();
+                    }
+                    KeyCode::Right
+                        if self.selected_column + 1 < self.table.num_columns() =>
+                    {
+                        self.selected_column += 1;
+                        self.adjust_horizontal_offset();
+                    }
+                    KeyCode::Char('[') => {
+                        self.sort_table(true, false); 
+                    }
+                    KeyCode::Char(']') => {
+                        self.sort_table(false, false); 
+                    }
+                    KeyCode::Char('-') if self.table.num_columns() > 0 => self.hide_column(),
+                    KeyCode::Char('H') if self.table.num_columns() > 0 => {
+                        self.move_column(false);
+                    }
+                    KeyCode::Char('L') if self.table.num_columns() > 0 => {
+                        self.move_column(true);
+                    }
+                    KeyCode::Char('P') if self.table.num_columns() > 0 => self.toggle_pin(),
+                    KeyCode::Char('{') => self.sort_table(true, true),
+                    KeyCode::Char('}') => self.sort_table(false, true),
+                    KeyCode::Char('o') => self.cycle_collation(),
+                    KeyCode::Char(' ') if self.table.num_columns() > 0 => {
+                        self.show_aggregation_popup = true;
+                        self.aggregation_state.select(Some(0));
+                    }
+                    KeyCode::Enter => {
+                        self.status_message = None;
+                        match self.open_selected() {
+                            Ok(action) => return action,
+                            Err(e) => self.status_message = Some(e.to_string()),
+                        }
+                    }
+                    KeyCode::Char('q') if self.unsaved_changes && !confirming_quit => {
+                        self.confirm_quit = true;
+                        self.status_message = Some(
+                            "Unsaved changes: q again to quit without saving, Ctrl-S to save"
+                                .to_string(),
+                        );
+                    }
+                    KeyCode::Char('q') => {
+                        return SheetAction::Close;
+                    }
+                    _ => {}
                 }
             }
         }
+
+        SheetAction::Continue
     }
 
     fn adjust_horizontal_offset(&mut self) {
+        // Pinned columns are always drawn; only the columns after them scroll.
+        let pinned = self.pinned_columns.min(self.table.num_columns());
+        let mut pinned_width = 0;
+        for i in 0..pinned {
+            pinned_width += self.get_column_width(i) + COLUMN_SPACING;
+        }
+        if self.selected_column < pinned {
+            return;
+        }
 
         let mut col_start = 0;
-        for i in 0..self.selected_column {
-            col_start += self.get_column_width(i) + 1;
+        for i in pinned..self.selected_column {
+            col_start += self.get_column_width(i) + COLUMN_SPACING;
         }
     
 
         let selected_col_width = self.get_column_width(self.selected_column);
     
 
-        let visible_width = self.table_area_width.saturating_sub(2);
+        // Borders, the row marker and the pinned columns take up part of the table area.
+        let visible_width = self
+            .table_area_width
+            .saturating_sub(2 + HIGHLIGHT_SYMBOL.len() as u16 + pinned_width);
     
 
         if col_start < self.horizontal_offset {
@@ -264,251 +755,1118 @@ impl TuiApp {
         }
     }
 
-    fn get_column_width(&self, index: usize) -> u16 {
+    /// Hides the selected column; `gv` shows it again.
+    fn hide_column(&mut self) {
+        if self.table.num_columns() == 1 {
+            self.status_message = Some("Cannot hide the last column".to_string());
+            return;
+        }
+        self.record(format!("hide {}", self.table.header(self.selected_column)));
+        let col = self.table.hide_column(self.selected_column);
+        if self.selected_column < self.pinned_columns {
+            self.pinned_columns -= 1;
+        }
+        self.selected_column = self.selected_column.min(self.table.num_columns() - 1);
+        self.status_message = Some(format!(
+            "Hid {}, gv to show hidden columns",
+            self.table.data.headers[col]
+        ));
+        self.adjust_horizontal_offset();
+    }
+
+    fn unhide_columns(&mut self) {
+        if self.table.num_columns() == self.table.data.headers.len() {
+            self.status_message = Some("No hidden columns".to_string());
+            return;
+        }
+        self.record("show hidden columns");
+        let selected = self.selected_data_column();
+        let count = self.table.unhide_columns();
+        self.status_message = Some(format!("Showing {} hidden columns", count));
+        self.selected_column = (0..self.table.num_columns())
+            .position(|col| self.table.data_column(col) == selected)
+            .unwrap_or(0);
+        self.adjust_horizontal_offset();
+    }
+
+    /// Swaps the selected column with its neighbour, keeping it selected.
+    fn move_column(&mut self, right: bool) {
+        let from = self.selected_column;
+        let to = if right { from + 1 } else { from.wrapping_sub(1) };
+        if to >= self.table.num_columns() {
+            return;
+        }
+        let direction = if right { "right" } else { "left" };
+        self.record(format!("move {} {}", self.table.header(from), direction));
+        self.table.move_column(from, to);
+        self.selected_column = to;
+        self.adjust_horizontal_offset();
+    }
+
+    /// Pins the columns up to and including the selected one, or unpins
+    /// them when the selected column is the last pinned one.
+    fn toggle_pin(&mut self) {
+        self.record(if self.pinned_columns == self.selected_column + 1 {
+            "unpin columns".to_string()
+        } else {
+            format!("pin {} columns", self.selected_column + 1)
+        });
+        if self.pinned_columns == self.selected_column + 1 {
+            self.pinned_columns = 0;
+            self.status_message = Some("Unpinned columns".to_string());
+        } else {
+            self.pinned_columns = self.selected_column + 1;
+            self.status_message = Some(format!("Pinned {} columns", self.pinned_columns));
+        }
+        self.horizontal_offset = 0;
+        self.adjust_horizontal_offset();
+    }
+
+    /// Width of the column shown at `col`.
+    fn get_column_width(&mut self, col: usize) -> u16 {
+        let index = self.table.data_column(col);
         match self.column_widths[index] {
             ColumnWidth::Fixed(w) => w,
             ColumnWidth::Content => {
-                let max_content_width = self.table.data.columns[index]
-                    .iter()
-                    .map(|cell| cell.len() as u16)
-                    .max()
-                    .unwrap_or(10)
-                    + 2;
-                max_content_width
+                if let Some(width) = self.content_widths[index] {
+                    return width;
+                }
+                let width = self.measure_content_width(index);
+                self.content_widths[index] = Some(width);
+                width
             }
         }
     }
 
+    /// Widest cell in a data column, header and type included, looking at an
+    /// evenly spaced sample of the shown rows so that huge tables do not have
+    /// to be scanned in full.
+    fn measure_content_width(&self, index: usize) -> u16 {
+        let data = &self.table.data;
+        let column = &data.columns[index];
+        let sort = self.sort_indicator(index).map_or(0, |s| s.chars().count() + 1);
+        let header = data.headers[index].chars().count()
+            + 1
+            + data.column_type(index).label().len()
+            + sort;
+        let rows = self.table.rows();
+        let step = (rows.len() / WIDTH_SAMPLE_ROWS).max(1);
+        rows.iter()
+            .step_by(step)
+            .map(|&row| column[row].chars().count())
+            .fold(header, usize::max)
+            .min(u16::MAX as usize - 2) as u16
+            + 2
+    }
+
+    /// Drops everything derived from the cell values; call after the data changes.
+    fn invalidate_caches(&mut self) {
+        self.content_widths.clear();
+        self.content_widths.resize(self.table.data.headers.len(), None);
+        self.aggregation_cache = None;
+    }
+
+    /// Moves `row_offset` just enough for the selected row to be on screen.
+    fn scroll_to_selected(&mut self, visible_rows: usize) {
+        if self.selected_row < self.row_offset {
+            self.row_offset = self.selected_row;
+        } else if visible_rows > 0 && self.selected_row >= self.row_offset + visible_rows {
+            self.row_offset = self.selected_row + 1 - visible_rows;
+        }
+    }
+
 
-    fn draw_ui<B: Backend>(
+    /// Draws the sheet over the whole terminal.
+    pub fn draw_ui<B: Backend>(
         &mut self,
         terminal: &mut Terminal<B>,
     ) -> Result<(), Box<dyn Error>> {
         terminal.draw(|f| {
-            let size = f.area();
-
-            f.render_widget(Block::default(), size);
-
-
-            let show_aggregation_block = !self.selected_aggregations.is_empty();
-            let agg_results = if show_aggregation_block {
-                Some(self.calculate_aggregations())
-            } else {
-                None
-            };
-
-
-            let agg_table_height = if let Some(agg_results) = &agg_results {
+            let area = f.area();
+            self.render(f, area);
+        })?;
+        Ok(())
+    }
 
-                let num_rows = agg_results.len() as u16;
-                3 + num_rows
-            } else {
-                0
-            };
+    /// Draws the sheet into part of a frame, for embedding it in a larger UI.
+    pub fn render(&mut self, f: &mut Frame, size: Rect) {
 
-            let constraints = if show_aggregation_block {
-                vec![Constraint::Min(0), Constraint::Length(agg_table_height)]
-            } else {
-                vec![Constraint::Percentage(100)]
-            };
+        f.render_widget(Block::default(), size);
 
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints(constraints)
-                .split(size);
 
-            self.table_area_width = chunks[0].width;
+        let show_aggregation_block = !self.selected_aggregations.is_empty();
+        let agg_results = if show_aggregation_block {
+            if self.aggregation_cache.is_none() {
+                self.aggregation_cache = Some(self.calculate_aggregations());
+            }
+            self.aggregation_cache.clone()
+        } else {
+            None
+        };
 
 
-            {
-                let header_cells = self.table.data.headers.iter().enumerate().map(|(i, h)| {
-                    let style = if i == self.selected_column {
-                        Style::default()
-                            .fg(Color::Yellow)
-                            .add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
-                            .bg(Color::Blue)
-                    } else {
-                        Style::default()
-                            .fg(Color::Yellow)
-                            .add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
-                    };
-                    Cell::from(h.clone()).style(style)
-                });
+        let agg_table_height = if let Some(agg_results) = &agg_results {
 
-                let header = Row::new(header_cells).height(1).bottom_margin(0);
+            let num_rows = agg_results.len() as u16;
+            3 + num_rows
+        } else {
+            0
+        };
 
-                let num_rows = if self.table.data.columns.is_empty() {
-                    0
-                } else {
-                    self.table.data.columns[0].len()
-                };
+        let mut constraints = if show_aggregation_block {
+            vec![Constraint::Min(0), Constraint::Length(agg_table_height)]
+        } else {
+            vec![Constraint::Percentage(100)]
+        };
+        let mut footer_line = || {
+            constraints[0] = Constraint::Min(0);
+            constraints.push(Constraint::Length(1));
+            constraints.len() - 1
+        };
+        let gauge_index = self.background_load.is_some().then(&mut footer_line);
+        let prompt_index = (self.search_prompt.is_some()
+            || self.filter_prompt.is_some()
+            || self.edit_prompt.is_some()
+            || self.save_prompt.is_some()
+            || self.command_prompt.is_some())
+        .then(&mut footer_line);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(constraints)
+            .split(size);
 
-                let rows = (0..num_rows).map(|row_idx| {
-                    let cells = self.table.data.columns.iter().enumerate().map(|(col_idx, col)| {
-                        let mut cell = Cell::from(col[row_idx].clone());
-                        if row_idx == self.selected_row && col_idx == self.selected_column {
-                            cell = cell.style(Style::default().bg(Color::LightBlue));
-                        }
-                        cell
-                    });
-                    Row::new(cells).height(1).bottom_margin(0)
-                });
+        self.table_area_width = chunks[0].width;
 
-                let widths = self
-                    .column_widths
-                    .iter()
-                    .enumerate()
-                    .map(|(i, width)| match width {
-                        ColumnWidth::Fixed(w) => Constraint::Length(*w),
-                        ColumnWidth::Content => {
-                            let max_content_width = self.table.data.columns[i]
-                                .iter()
-                                .map(|cell| cell.len() as u16)
-                                .max()
-                                .unwrap_or(10)
-                                + 2;
-                            Constraint::Length(max_content_width)
-                        }
-                    })
-                    .collect::<Vec<_>>();
 
-                let table = Table::new(rows, &widths)
-                    .header(header)
-                    .block(Block::default().borders(Borders::ALL).title("Table"))
-                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
-                    .highlight_symbol("-> ")
-                    .column_spacing(2);
+        {
+            let column_widths: Vec<u16> = (0..self.table.num_columns())
+                .map(|i| self.get_column_width(i))
+                .collect();
 
-                f.render_stateful_widget(table, chunks[0], &mut self.table_state);
+            // Pinned columns come first; of the others, those starting left of
+            // the horizontal offset are scrolled out of view.
+            let pinned = self.pinned_columns.min(self.table.num_columns());
+            let mut first_column = pinned;
+            let mut col_start = 0;
+            while first_column < self.selected_column && col_start < self.horizontal_offset {
+                col_start += column_widths[first_column] + COLUMN_SPACING;
+                first_column += 1;
+            }
+            // Only the columns that fit are drawn, the last one cut short, so
+            // that the table never squeezes them all to make room.
+            let mut room = chunks[0]
+                .width
+                .saturating_sub(2 + HIGHLIGHT_SYMBOL.len() as u16);
+            let mut shown = Vec::new();
+            let mut widths = Vec::new();
+            for col in (0..pinned).chain(first_column..self.table.num_columns()) {
+                if room == 0 {
+                    break;
+                }
+                let width = column_widths[col].min(room);
+                shown.push(col);
+                widths.push(Constraint::Length(width));
+                room = room.saturating_sub(width + COLUMN_SPACING);
             }
 
-            if let Some(agg_results) = &agg_results {
-                
-                let mut all_aggs = HashSet::new();
-                for aggs in self.selected_aggregations.values() {
-                    for &agg in aggs {
-                        all_aggs.insert(agg);
-                    }
+            // Borders take two lines and the header one.
+            let visible_rows = chunks[0].height.saturating_sub(3) as usize;
+            self.visible_rows = visible_rows;
+            self.scroll_to_selected(visible_rows);
+
+            let table = &self.table;
+            let numeric: Vec<bool> = (0..table.num_columns())
+                .map(|col| table.column_type(col).is_numeric())
+                .collect();
+            let alignment = |col: usize| {
+                if numeric[col] {
+                    Alignment::Right
+                } else {
+                    Alignment::Left
                 }
-                let mut all_aggs_vec: Vec<_> = all_aggs.into_iter().collect();
-                all_aggs_vec.sort();
+            };
+            let type_style = Style::default().fg(Color::DarkGray);
+            let sort_style = Style::default().fg(Color::Magenta);
 
-                let mut header_cells = vec![
-                    Cell::from("Column").style(Style::default().add_modifier(Modifier::BOLD)),
+            let header_cells = shown.iter().map(|&i| {
+                // Group keys stand out from the other headers.
+                let color = if self.group_keys.contains(&table.data_column(i)) {
+                    Color::Cyan
+                } else {
+                    Color::Yellow
+                };
+                let style = if i == self.selected_column {
+                    Style::default()
+                        .fg(color)
+                        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+                        .bg(Color::Blue)
+                } else {
+                    Style::default()
+                        .fg(color)
+                        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+                };
+                let mut label = vec![
+                    Span::raw(table.header(i).to_string()),
+                    Span::styled(format!(" {}", table.column_type(i).label()), type_style),
                 ];
-                for agg in &all_aggs_vec {
-                    header_cells.push(
-                        Cell::from(format!("{:?}", agg))
-                            .style(Style::default().add_modifier(Modifier::BOLD)),
-                    );
+                if let Some(indicator) = self.sort_indicator(table.data_column(i)) {
+                    label.push(Span::styled(format!(" {}", indicator), sort_style));
                 }
-                let header = Row::new(header_cells).height(1).bottom_margin(0);
+                let label = Line::from(label).alignment(alignment(i));
+                Cell::from(label).style(style)
+            });
+
+            let header = Row::new(header_cells).height(1).bottom_margin(0);
+
+            let mut title = vec![Span::raw(self.title.clone())];
+            if let Some(applied) = &self.filter {
+                title.push(Span::styled(
+                    format!(
+                        " | filtered {} of {}",
+                        table.num_rows(),
+                        applied.unfiltered_rows.len()
+                    ),
+                    Style::default().fg(Color::Cyan),
+                ));
+            }
+            if let Some(message) = &self.status_message {
+                title.push(Span::raw(" | "));
+                title.push(Span::styled(message.clone(), Style::default().fg(Color::Red)));
+            }
+            let title = Line::from(title);
 
-                let mut rows = Vec::new();
+            let num_rows = table.num_rows();
 
-                let mut col_indices: Vec<_> = agg_results.keys().cloned().collect();
-                col_indices.sort();
+            let null_style = Style::default()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::ITALIC);
 
-                for &col_idx in &col_indices {
-                    let col_aggs = &agg_results[&col_idx];
-                    let mut cells = vec![Cell::from(self.table.data.headers[col_idx].clone())];
-                    for agg in &all_aggs_vec {
-                        if let Some(result_option) = col_aggs.get(agg) {
-                            if let Some(result) = result_option {
-                                cells.push(Cell::from(result.clone()));
-                            } else {
-                                cells.push(Cell::from("-"));
-                            }
-                        } else {
-                            
-                            cells.push(Cell::from("-"));
-                        }
-                    }
-                    rows.push(Row::new(cells).height(1).bottom_margin(0));
-                }
+            let first_row = self.row_offset.min(num_rows);
+            let last_row = (first_row + visible_rows).min(num_rows);
+            let match_style = Style::default().fg(Color::Black).bg(Color::Yellow);
+            let modified_style = Style::default().fg(Color::LightGreen);
+            let search = self.search.as_ref();
 
-                if !rows.is_empty() {
-                    
-                    let mut widths = Vec::new();
-                    widths.push(Constraint::Length(15)); 
-                    for _ in &all_aggs_vec {
-                        widths.push(Constraint::Length(15));
+            let rows = (first_row..last_row).map(|row_idx| {
+                let cells = shown.iter().map(|&col_idx| {
+                    let mut cell = if table.is_null(col_idx, row_idx) {
+                        Cell::from(Line::from(NULL_DISPLAY).alignment(alignment(col_idx)))
+                            .style(null_style)
+                    } else {
+                        Cell::from(
+                            Line::from(table.cell(col_idx, row_idx)).alignment(alignment(col_idx)),
+                        )
+                    };
+                    if self.is_modified(table.data_column(col_idx), table.data_row(row_idx)) {
+                        cell = cell.style(modified_style);
                     }
+                    if row_idx == self.selected_row && col_idx == self.selected_column {
+                        cell = cell.style(Style::default().bg(Color::LightBlue));
+                    } else if search.is_some_and(|s| s.matches(table, col_idx, row_idx)) {
+                        cell = cell.style(match_style);
+                    }
+                    cell
+                });
+                Row::new(cells).height(1).bottom_margin(0)
+            });
+
+            let table_widget = Table::new(rows, &widths)
+                .header(header)
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                .highlight_symbol(HIGHLIGHT_SYMBOL)
+                .column_spacing(COLUMN_SPACING);
+
+            let mut table_state = TableState::default()
+                .with_selected((num_rows > 0).then(|| self.selected_row - first_row));
+            f.render_stateful_widget(table_widget, chunks[0], &mut table_state);
+        }
 
-                    
-                    let agg_table = Table::new(rows, &widths)
-                        .header(header)
-                        .block(Block::default().borders(Borders::ALL).title("Aggregations"))
-                        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
-                        .column_spacing(1);
-
-                    
-                    f.render_widget(agg_table, chunks[1]);
+        if let Some(agg_results) = &agg_results {
+            
+            let mut all_aggs = HashSet::new();
+            for aggs in self.selected_aggregations.values() {
+                for &agg in aggs {
+                    all_aggs.insert(agg);
                 }
             }
+            let mut all_aggs_vec: Vec<_> = all_aggs.into_iter().collect();
+            all_aggs_vec.sort();
+
+            let mut header_cells = vec![
+                Cell::from("Column").style(Style::default().add_modifier(Modifier::BOLD)),
+            ];
+            for agg in &all_aggs_vec {
+                header_cells.push(
+                    Cell::from(format!("{:?}", agg))
+                        .style(Style::default().add_modifier(Modifier::BOLD)),
+                );
+            }
+            let header = Row::new(header_cells).height(1).bottom_margin(0);
 
-            if self.show_aggregation_popup {
-                
-                let popup_area = Self::centered_rect(60, 40, size);
+            let mut rows = Vec::new();
 
-                
-                let block = Block::default()
-                    .title("Select aggregation functions (q to quit)")
-                    .borders(Borders::ALL)
-                    .style(Style::default().bg(Color::Black));
+            let mut col_indices: Vec<_> = agg_results.keys().cloned().collect();
+            col_indices.sort();
 
-                
-                let inner_area = block.inner(popup_area);
+            // Columns are as wide as their longest entry, header included.
+            let mut text_widths: Vec<usize> = std::iter::once("Column".len())
+                .chain(all_aggs_vec.iter().map(|agg| format!("{:?}", agg).len()))
+                .collect();
+            for &col_idx in &col_indices {
+                let col_aggs = &agg_results[&col_idx];
+                let mut texts = vec![self.table.data.headers[col_idx].clone()];
+                for agg in &all_aggs_vec {
+                    texts.push(
+                        col_aggs
+                            .get(agg)
+                            .map_or_else(|| "-".to_string(), AggregationResult::display),
+                    );
+                }
+                for (width, text) in text_widths.iter_mut().zip(&texts) {
+                    *width = (*width).max(text.chars().count());
+                }
+                rows.push(Row::new(texts).height(1).bottom_margin(0));
+            }
 
+            if !rows.is_empty() {
                 
-                f.render_widget(Clear, popup_area);
+                let widths: Vec<Constraint> = text_widths
+                    .iter()
+                    .map(|&w| Constraint::Length(w.min(u16::MAX as usize) as u16))
+                    .collect();
 
-                
-                f.render_widget(block, popup_area);
+                let title = format!("Aggregations ({})", self.unparseable_policy.label());
+                let agg_table = Table::new(rows, &widths)
+                    .header(header)
+                    .block(Block::default().borders(Borders::ALL).title(title))
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                    .column_spacing(1);
 
                 
-                let list_height = AggregationFunction::iter().count() as u16;
-
-                
-                let available_height = inner_area.height;
+                f.render_widget(agg_table, chunks[1]);
+            }
+        }
 
-                
-                let top_padding = (available_height.saturating_sub(list_height)) / 2;
+        if let Some(load) = &self.background_load {
+            let bytes_read = load.bytes_read();
+            let rows = self.table.num_rows();
+            let (ratio, read) = match load.total_bytes {
+                Some(total) if total > 0 => (
+                    (bytes_read as f64 / total as f64).min(1.0),
+                    format!("{} / {}", format_bytes(bytes_read), format_bytes(total)),
+                ),
+                _ => (0.0, format_bytes(bytes_read)),
+            };
+            let label = if load.is_cancelled() {
+                format!("Stopping after {} rows...", rows)
+            } else {
+                format!("Loading {}, {} rows (Ctrl-C to stop)", read, rows)
+            };
+            let gauge = Gauge::default()
+                .gauge_style(Style::default().fg(Color::Blue).bg(Color::Black))
+                .ratio(ratio)
+                .label(label);
+            if let Some(index) = gauge_index {
+                f.render_widget(gauge, chunks[index]);
+            }
+        }
 
-                
-                let layout = Layout::default()
-                    .direction(Direction::Vertical)
-                    .constraints([
-                        Constraint::Length(top_padding),
-                        Constraint::Length(list_height),
-                        Constraint::Min(0),
-                    ])
-                    .split(inner_area);
+        if let (Some(state), Some(index)) = (&self.search_prompt, prompt_index) {
+            let mut extra = vec![Span::styled(
+                state.options.describe(),
+                Style::default().fg(Color::DarkGray),
+            )];
+            if let Some(error) = &state.error {
+                extra.push(Span::styled(
+                    format!("  {}", error),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            state.prompt.render(f, chunks[index], extra);
+        }
 
-                
-                let items: Vec<ListItem> = AggregationFunction::iter()
-                    .map(|agg| {
-                        let is_selected = self
-                            .selected_aggregations
-                            .get(&self.selected_column)
-                            .map_or(false, |v| v.contains(&agg));
-                        let checkbox = if is_selected { "[x]" } else { "[ ]" };
-                        let content = format!("{} {:?}", checkbox, agg);
-                        ListItem::new(content)
-                    })
-                    .collect();
+        if let (Some(state), Some(index)) = (&self.filter_prompt, prompt_index) {
+            let hint = if state.new_sheet {
+                "Enter: open as new sheet"
+            } else {
+                "Enter: apply, empty to clear"
+            };
+            let mut extra = vec![Span::styled(hint, Style::default().fg(Color::DarkGray))];
+            if let Some(error) = &state.error {
+                extra.push(Span::styled(
+                    format!("  {}", error),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            state.prompt.render(f, chunks[index], extra);
+        }
+
+        if let (Some(state), Some(index)) = (&self.save_prompt, prompt_index) {
+            let target = if state.aggregations {
+                "[aggregations]"
+            } else {
+                "[view]"
+            };
+            let mut extra = vec![Span::styled(target, Style::default().fg(Color::DarkGray))];
+            if let Some(error) = &state.error {
+                extra.push(Span::styled(
+                    format!("  {}", error),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            state.prompt.render(f, chunks[index], extra);
+        }
 
-                let list = List::new(items)
-                    .highlight_style(Style::default().fg(Color::Yellow).bg(Color::Blue))
-                    .highlight_symbol(">> ");
+        if let (Some(state), Some(index)) = (&self.edit_prompt, prompt_index) {
+            let (col, row) = state.cell;
+            let hint = format!("{} of row {}", self.table.data.headers[col], row + 1);
+            let extra = vec![Span::styled(hint, Style::default().fg(Color::DarkGray))];
+            state.prompt.render(f, chunks[index], extra);
+        }
 
-                
-                f.render_stateful_widget(list, layout[1], &mut self.aggregation_state);
+        if let (Some(state), Some(index)) = (&self.command_prompt, prompt_index) {
+            let mut extra = Vec::new();
+            if let Some(error) = &state.error {
+                extra.push(Span::styled(
+                    format!("  {}", error),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            state.prompt.render(f, chunks[index], extra);
+        }
+
+        if self.show_aggregation_popup {
+            
+            let popup_area = Self::centered_rect(60, 70, size);
+
+            
+            let block = Block::default()
+                .title(format!(
+                    "Select aggregation functions (s: {}, q to quit)",
+                    self.unparseable_policy.label()
+                ))
+                .borders(Borders::ALL)
+                .style(Style::default().bg(Color::Black));
+
+            
+            let inner_area = block.inner(popup_area);
+
+            
+            f.render_widget(Clear, popup_area);
+
+            
+            f.render_widget(block, popup_area);
+
+            
+            let list_height = AggregationFunction::iter().count() as u16;
+
+            
+            let available_height = inner_area.height;
+
+            
+            let top_padding = (available_height.saturating_sub(list_height)) / 2;
+
+            
+            let layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(top_padding),
+                    Constraint::Length(list_height),
+                    Constraint::Min(0),
+                ])
+                .split(inner_area);
+
+            
+            let items: Vec<ListItem> = AggregationFunction::iter()
+                .map(|agg| {
+                    let is_selected = self
+                        .selected_aggregations
+                        .get(&self.table.data_column(self.selected_column))
+                        .is_some_and(|v| v.contains(&agg));
+                    let checkbox = if is_selected { "[x]" } else { "[ ]" };
+                    let content = format!("{} {:?}", checkbox, agg);
+                    ListItem::new(content)
+                })
+                .collect();
+
+            let list = List::new(items)
+                .highlight_style(Style::default().fg(Color::Yellow).bg(Color::Blue))
+                .highlight_symbol(">> ");
+
+            
+            f.render_stateful_widget(list, layout[1], &mut self.aggregation_state);
+        }
+    }
+
+    fn open_search_prompt(&mut self) {
+        // Start from the last search's settings, scoped to the current column.
+        let mut options = match &self.search {
+            Some(search) => search.options.clone(),
+            None => SearchOptions {
+                text: String::new(),
+                regex: false,
+                case_sensitive: false,
+                scope: SearchScope::Column(self.selected_data_column()),
+            },
+        };
+        if let SearchScope::Column(_) = options.scope {
+            options.scope = SearchScope::Column(self.selected_data_column());
+        }
+        self.search_prompt = Some(SearchPrompt {
+            prompt: Prompt::new("/").with_input(options.text.clone()),
+            options,
+            origin: (self.selected_row, self.selected_column),
+            error: None,
+        });
+        self.status_message = None;
+    }
+
+    /// Ctrl-R switches between text and regex, Ctrl-T toggles case
+    /// sensitivity and Tab the scope; the cursor follows the first hit as
+    /// the search is typed.
+    fn handle_search_key(&mut self, key: KeyEvent) {
+        let Some(state) = &mut self.search_prompt else {
+            return;
+        };
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('r') if control => state.options.regex = !state.options.regex,
+            KeyCode::Char('t') if control => {
+                state.options.case_sensitive = !state.options.case_sensitive
+            }
+            KeyCode::Tab => {
+                state.options.scope = match state.options.scope {
+                    SearchScope::Column(_) => SearchScope::Table,
+                    SearchScope::Table => {
+                        SearchScope::Column(self.table.data_column(state.origin.1))
+                    }
+                }
+            }
+            _ => match state.prompt.handle_key(key) {
+                PromptEvent::Changed => {}
+                PromptEvent::Submitted => {
+                    if let Some(state) = self.search_prompt.take() {
+                        if state.error.is_some() {
+                            self.status_message = state.error;
+                        }
+                    }
+                    return;
+                }
+                PromptEvent::Cancelled => {
+                    if let Some(state) = self.search_prompt.take() {
+                        (self.selected_row, self.selected_column) = state.origin;
+                        self.adjust_horizontal_offset();
+                    }
+                    self.search = None;
+                    return;
+                }
+                PromptEvent::Moved | PromptEvent::Ignored => return,
+            },
+        }
+        self.update_search();
+    }
+
+    /// Recompiles the search being typed and moves to its first hit from
+    /// where the search started.
+    fn update_search(&mut self) {
+        let Some(state) = &mut self.search_prompt else {
+            return;
+        };
+        state.options.text = state.prompt.input.clone();
+        state.error = None;
+        let origin = state.origin;
+        let mut hit = None;
+        self.search = None;
+
+        if !state.options.text.is_empty() {
+            match Search::new(state.options.clone()) {
+                Ok(search) => {
+                    hit = search.find(&self.table, origin, true, true);
+                    if hit.is_none() {
+                        state.error = Some("No match".to_string());
+                    }
+                    self.search = Some(search);
+                }
+                Err(_) => state.error = Some("Invalid regex".to_string()),
+            }
+        }
+
+        (self.selected_row, self.selected_column) = hit.unwrap_or(origin);
+        self.adjust_horizontal_offset();
+    }
+
+    fn jump_to_match(&mut self, forward: bool) {
+        let Some(search) = &self.search else {
+            self.status_message = Some("No search, press / to start one".to_string());
+            return;
+        };
+        let from = (self.selected_row, self.selected_column);
+        match search.find(&self.table, from, forward, false) {
+            Some(hit) => {
+                (self.selected_row, self.selected_column) = hit;
+                self.status_message = None;
+                self.adjust_horizontal_offset();
+            }
+            None => {
+                self.status_message = Some(format!("No match for {}", search.options.text));
+            }
+        }
+    }
+
+    fn open_filter_prompt(&mut self, new_sheet: bool) {
+        let current = match (&self.filter, new_sheet) {
+            (Some(applied), false) => applied.filter.text.clone(),
+            _ => String::new(),
+        };
+        self.filter_prompt = Some(FilterPrompt {
+            prompt: Prompt::new("filter:").with_input(current),
+            new_sheet,
+            error: None,
+        });
+    }
+
+    /// Applies the filter when it is submitted. Filters for a new sheet
+    /// return that sheet; a parse error keeps the prompt open.
+    fn handle_filter_key(&mut self, key: KeyEvent) -> Option<TuiApp> {
+        let state = self.filter_prompt.as_mut()?;
+        match state.prompt.handle_key(key) {
+            PromptEvent::Submitted => {}
+            PromptEvent::Cancelled => {
+                self.filter_prompt = None;
+                return None;
             }
+            PromptEvent::Changed => {
+                state.error = None;
+                return None;
+            }
+            PromptEvent::Moved | PromptEvent::Ignored => return None,
+        }
+
+        let text = state.prompt.input.trim().to_string();
+        let new_sheet = state.new_sheet;
+        if text.is_empty() {
+            self.filter_prompt = None;
+            if !new_sheet {
+                self.clear_filter();
+            }
+            return None;
+        }
+
+        let filter = match Filter::parse(&text, &self.table.data) {
+            Ok(filter) => filter,
+            Err(e) => {
+                if let Some(state) = &mut self.filter_prompt {
+                    state.error = Some(e.to_string());
+                }
+                return None;
+            }
+        };
+        self.filter_prompt = None;
+        if !new_sheet {
+            self.record(format!("filter {}", filter.text));
+        }
+
+        // A filter in place replaces the one before it rather than narrowing it.
+        let base = match (self.filter.take(), new_sheet) {
+            (Some(applied), false) => applied.unfiltered_rows,
+            (applied, _) => {
+                self.filter = applied;
+                self.table.rows().to_vec()
+            }
+        };
+        let rows = filter.matching_rows(&self.table.data, base.iter().copied());
+        if new_sheet {
+            let title = format!("{} | {}", self.title, filter.text);
+            let table = VirtualTable::with_rows(Arc::clone(&self.table.data), Some(rows));
+            return Some(TuiApp::new(table).with_title(title));
+        }
+
+        self.table.set_rows(rows);
+        // A replaced filter starts again from rows in their order before any sort since.
+        if !self.sort_keys.is_empty() {
+            self.table.sort_by(&self.sort_keys, self.collation);
+        }
+        self.filter = Some(AppliedFilter {
+            filter,
+            unfiltered_rows: base,
+        });
+        self.reset_cursor();
+        None
+    }
+
+    fn clear_filter(&mut self) {
+        if self.filter.is_some() {
+            self.record("clear filter");
+        }
+        if let Some(applied) = self.filter.take() {
+            self.table.set_rows(applied.unfiltered_rows);
+            // The rows come back in the order they had, which a sort since may have changed.
+            if !self.sort_keys.is_empty() {
+                self.table.sort_by(&self.sort_keys, self.collation);
+            }
+            self.reset_cursor();
+        }
+    }
+
+    /// Offers the file the sheet came from when there are changes to save back.
+    fn open_save_prompt(&mut self) {
+        let path = match &self.origin {
+            Some(origin) if self.unsaved_changes => origin.path.clone(),
+            _ => String::new(),
+        };
+        self.save_prompt = Some(SavePrompt {
+            prompt: Prompt::new("save as:").with_input(path),
+            aggregations: false,
+            overwrite: None,
+            error: None,
+        });
+    }
+
+    /// Tab switches between saving the view and the aggregation results;
+    /// the format follows the file extension.
+    fn handle_save_key(&mut self, key: KeyEvent) {
+        let Some(state) = &mut self.save_prompt else {
+            return;
+        };
+        if key.code == KeyCode::Tab {
+            if self.selected_aggregations.is_empty() {
+                state.error = Some("No aggregations selected".to_string());
+            } else {
+                state.aggregations = !state.aggregations;
+            }
+            return;
+        }
+        match state.prompt.handle_key(key) {
+            PromptEvent::Submitted => {}
+            PromptEvent::Cancelled => {
+                self.save_prompt = None;
+                return;
+            }
+            PromptEvent::Changed => {
+                state.error = None;
+                state.overwrite = None;
+                return;
+            }
+            PromptEvent::Moved | PromptEvent::Ignored => return,
+        }
+
+        let path = state.prompt.input.trim().to_string();
+        if path.is_empty() {
+            return;
+        }
+        if Path::new(&path).exists() && state.overwrite.as_deref() != Some(path.as_str()) {
+            state.error = Some(format!("{} exists, Enter again to overwrite", path));
+            state.overwrite = Some(path);
+            return;
+        }
+
+        let aggregations = state.aggregations;
+        match self.save(&path, aggregations) {
+            Ok((rows, to_origin)) => {
+                self.save_prompt = None;
+                self.status_message = Some(format!("Saved {} rows to {}", rows, path));
+                // A copy of the view leaves the edits still to be saved to the source.
+                if to_origin {
+                    self.mark_saved();
+                    self.edited_cells.clear();
+                    self.inserted_rows.clear();
+                }
+            }
+            Err(e) => {
+                if let Some(state) = &mut self.save_prompt {
+                    state.error = Some(e.to_string());
+                    state.overwrite = None;
+                }
+            }
+        }
+    }
+
+    /// Runs a command when it is submitted; an error keeps the prompt open.
+    fn handle_command_key(&mut self, key: KeyEvent) -> Option<TuiApp> {
+        let state = self.command_prompt.as_mut()?;
+        match state.prompt.handle_key(key) {
+            PromptEvent::Submitted => {}
+            PromptEvent::Cancelled => {
+                self.command_prompt = None;
+                return None;
+            }
+            PromptEvent::Changed => {
+                state.error = None;
+                return None;
+            }
+            PromptEvent::Moved | PromptEvent::Ignored => return None,
+        }
+
+        let text = state.prompt.input.trim().to_string();
+        let (command, argument) = text.split_once(char::is_whitespace).unwrap_or((&text, ""));
+        let result = match command {
+            "" => {
+                self.command_prompt = None;
+                return None;
+            }
+            "sql" => self.run_sql(argument.trim()),
+            _ => Err(format!("Unknown command '{}'", command).into()),
+        };
+        match result {
+            Ok(new_app) => {
+                self.command_prompt = None;
+                Some(new_app)
+            }
+            Err(e) => {
+                if let Some(state) = &mut self.command_prompt {
+                    state.error = Some(e.to_string());
+                }
+                None
+            }
+        }
+    }
+
+    /// Runs a query as a new sheet. `t` is this sheet; files are named by
+    /// quoted path the first time and by alias after that.
+    fn run_sql(&mut self, query: &str) -> Result<TuiApp, Box<dyn Error>> {
+        let table = &self.table;
+        let open_tables = &mut self.open_tables;
+        let result = sql::run_query(query, |name: TableName| {
+            if !name.quoted && name.name.eq_ignore_ascii_case("t") {
+                return Ok(table.clone());
+            }
+            if let Some(data) = open_tables.get(name.name) {
+                return Ok(VirtualTable::with_rows(Arc::clone(data), None));
+            }
+            if !name.quoted {
+                return Err(format!(
+                    "Unknown table '{}', use t for this sheet or a quoted file path",
+                    name.name
+                )
+                .into());
+            }
+            let data = Arc::new(Source::open(name.name, None, None, None)?.load()?);
+            let file = Path::new(name.name).file_name().and_then(|f| f.to_str());
+            if let Some(alias) = file.and_then(|f| f.split('.').next()) {
+                open_tables.insert(alias.to_string(), Arc::clone(&data));
+            }
+            open_tables.insert(name.name.to_string(), Arc::clone(&data));
+            Ok(VirtualTable::with_rows(data, None))
         })?;
+
+        Ok(TuiApp::new(result).with_title(format!("{} | sql", self.title)))
+    }
+
+    /// Writes the view, or the aggregation results, and returns the rows
+    /// written and whether the file the sheet came from was rewritten.
+    ///
+    /// Saving to the file the sheet came from rewrites all of it in its own
+    /// dialect: every column, and every row left after deletions, including
+    /// those a filter hides.
+    fn save(&self, path: &str, aggregations: bool) -> Result<(usize, bool), Box<dyn Error>> {
+        let origin = self.origin.as_ref().filter(|o| !aggregations && same_file(&o.path, path));
+        if let Some(origin) = origin {
+            let rows = self.source_rows();
+            let table = VirtualTable::with_rows(Arc::clone(&self.table.data), Some(rows));
+            DelimitedWriter::with_dialect(origin.dialect).save(&table, path)?;
+            return Ok((table.num_rows(), true));
+        }
+
+        let writer = writer_for_path(path)?;
+        let results;
+        let table = if aggregations {
+            results = VirtualTable::new(self.aggregation_table());
+            &results
+        } else {
+            &self.table
+        };
+        writer.save(table, path)?;
+        Ok((table.num_rows(), false))
+    }
+
+    /// The rows saving back writes: those not deleted, in the order of the
+    /// file rather than the view, with inserted rows below the row they
+    /// were added under.
+    fn source_rows(&self) -> Vec<usize> {
+        let kept: HashSet<usize> = match &self.filter {
+            Some(applied) => applied.unfiltered_rows.iter().copied().collect(),
+            None => self.table.rows().iter().copied().collect(),
+        };
+        // A row inserted later under the same row goes directly below it.
+        let mut below: HashMap<Option<usize>, Vec<usize>> = HashMap::new();
+        for (&row, &after) in &self.inserted_below {
+            below.entry(after).or_default().push(row);
+        }
+        for rows in below.values_mut() {
+            rows.sort_unstable();
+        }
+
+        let mut pending: Vec<usize> = (0..self.table.data.num_rows())
+            .rev()
+            .filter(|row| !self.inserted_below.contains_key(row))
+            .collect();
+        pending.extend(below.get(&None).into_iter().flatten());
+        let mut rows = Vec::with_capacity(kept.len());
+        while let Some(row) = pending.pop() {
+            if kept.contains(&row) {
+                rows.push(row);
+            }
+            pending.extend(below.get(&Some(row)).into_iter().flatten());
+        }
+        rows
+    }
+
+    fn open_edit_prompt(&mut self) {
+        if let Err(e) = self.check_editable() {
+            self.status_message = Some(e.to_string());
+            return;
+        }
+        if self.table.num_rows() == 0 || self.table.num_columns() == 0 {
+            return;
+        }
+        let cell = (self.selected_data_column(), self.table.data_row(self.selected_row));
+        let value = self.cell_value(cell.0, cell.1).unwrap_or_default();
+        self.edit_prompt = Some(EditPrompt {
+            prompt: Prompt::new("edit:").with_input(value),
+            cell,
+        });
+    }
+
+    fn handle_edit_key(&mut self, key: KeyEvent) {
+        let Some(state) = &mut self.edit_prompt else {
+            return;
+        };
+        match state.prompt.handle_key(key) {
+            PromptEvent::Submitted => {}
+            PromptEvent::Cancelled => {
+                self.edit_prompt = None;
+                return;
+            }
+            PromptEvent::Changed | PromptEvent::Moved | PromptEvent::Ignored => return,
+        }
+
+        let (col, row) = state.cell;
+        let value = Some(state.prompt.input.clone());
+        self.edit_prompt = None;
+        let old = self.cell_value(col, row);
+        if value == old {
+            return;
+        }
+
+        self.record_edit(
+            format!("edit {} of row {}", self.table.data.headers[col], row + 1),
+            Some(DataChange::Cell {
+                col,
+                row,
+                value: old.clone(),
+            }),
+        );
+        self.edited_cells.entry((col, row)).or_insert(old);
+        self.table.data_mut().set_cell(col, row, value);
+        self.unsaved_changes = true;
+        self.invalidate_caches();
+    }
+
+    /// Adds an empty row below the cursor.
+    fn insert_row(&mut self) {
+        if let Err(e) = self.check_editable() {
+            self.status_message = Some(e.to_string());
+            return;
+        }
+        self.record_edit("insert row", None);
+        let after = (self.table.num_rows() > 0).then(|| self.table.data_row(self.selected_row));
+        let row = self.table.data_mut().push_empty_row();
+
+        let position = after.map_or(0, |_| self.selected_row + 1);
+        let mut rows = self.table.rows().to_vec();
+        rows.insert(position, row);
+        self.table.set_rows(rows);
+        // Rows hidden by a filter keep their place around the new one.
+        if let Some(applied) = &mut self.filter {
+            let position = after
+                .and_then(|after| applied.unfiltered_rows.iter().position(|&r| r == after))
+                .map_or(applied.unfiltered_rows.len(), |position| position + 1);
+            applied.unfiltered_rows.insert(position, row);
+        }
+
+        self.inserted_rows.insert(row);
+        self.inserted_below.insert(row, after);
+        self.selected_row = position;
+        self.unsaved_changes = true;
+        self.invalidate_caches();
+    }
+
+    fn delete_row(&mut self) {
+        if let Err(e) = self.check_editable() {
+            self.status_message = Some(e.to_string());
+            return;
+        }
+        if self.table.num_rows() == 0 {
+            return;
+        }
+        let row = self.table.data_row(self.selected_row);
+        self.record_edit(format!("delete row {}", row + 1), None);
+
+        let mut rows = self.table.rows().to_vec();
+        rows.remove(self.selected_row);
+        self.table.set_rows(rows);
+        if let Some(applied) = &mut self.filter {
+            applied.unfiltered_rows.retain(|&r| r != row);
+        }
+
+        self.selected_row = self.selected_row.min(self.table.num_rows().saturating_sub(1));
+        self.unsaved_changes = true;
+        self.invalidate_caches();
+    }
+
+    /// Only plain tables are edited, only those read from a delimited file
+    /// that edits can be saved back to, and only once they are fully loaded.
+    fn check_editable(&self) -> Result<(), Box<dyn Error>> {
+        if !matches!(self.kind, SheetKind::Table) {
+            return Err("Only table sheets can be edited".into());
+        }
+        if self.origin.is_none() {
+            return Err("Only sheets read from a plain delimited file can be edited".into());
+        }
+        if self.background_load.is_some() {
+            return Err("Editing is available once loading finishes".into());
+        }
         Ok(())
     }
 
+    /// A cell by data column and row, `None` when null.
+    fn cell_value(&self, col: usize, row: usize) -> Option<String> {
+        let data = &self.table.data;
+        (!data.is_null(col, row)).then(|| data.columns[col][row].clone())
+    }
+
+    /// Whether a cell differs from when the sheet was last saved.
+    fn is_modified(&self, col: usize, row: usize) -> bool {
+        if self.inserted_rows.contains(&row) {
+            return true;
+        }
+        let data = &self.table.data;
+        match self.edited_cells.get(&(col, row)) {
+            None => false,
+            Some(None) => !data.is_null(col, row),
+            Some(Some(saved)) => data.is_null(col, row) || *saved != data.columns[col][row],
+        }
+    }
+
+    /// The aggregation results as a table with a row per column, as shown
+    /// below the view.
+    fn aggregation_table(&self) -> TableData {
+        let results = match &self.aggregation_cache {
+            Some(results) => results.clone(),
+            None => self.calculate_aggregations(),
+        };
+        results_table(&self.table.data, &results)
+    }
+
+    /// Goes back to the top left after the rows have been replaced.
+    fn reset_cursor(&mut self) {
+        self.selected_row = 0;
+        self.row_offset = 0;
+        self.invalidate_caches();
+    }
+
     fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         let popup_layout = Layout::default()
             .direction(Direction::Vertical)
@@ -538,100 +1896,670 @@ impl TuiApp {
 
     fn calculate_aggregations(
         &self,
-    ) -> HashMap<usize, HashMap<AggregationFunction, Option<String>>> {
-        let mut results = HashMap::new();
-
-        for (&col_idx, aggs) in &self.selected_aggregations {
-            let column_data = &self.table.data.columns[col_idx];
-            let mut agg_results = HashMap::new();
+    ) -> HashMap<usize, HashMap<AggregationFunction, AggregationResult>> {
+        self.selected_aggregations
+            .iter()
+            .filter(|(_, aggs)| !aggs.is_empty())
+            .map(|(&col_idx, aggs)| {
+                let results = aggregate_column(
+                    &self.table.data,
+                    col_idx,
+                    self.table.rows().iter().copied(),
+                    aggs,
+                    self.unparseable_policy,
+                );
+                (col_idx, results)
+            })
+            .collect()
+    }
 
-            for &agg in aggs {
-                let result = match agg {
-                    AggregationFunction::Sum => {
-                        
-                        let parsed_data: Vec<f64> = column_data
-                            .iter()
-                            .filter_map(|v| v.parse::<f64>().ok())
-                            .collect();
-
-                        if parsed_data.len() == column_data.len() && !parsed_data.is_empty() {
-                            
-                            let sum: f64 = parsed_data.iter().sum();
-                            Some(sum.to_string())
+    fn open_selected(&self) -> Result<SheetAction, Box<dyn Error>> {
+        match &self.kind {
+            SheetKind::Table if self.table.num_rows() == 0 || self.table.num_columns() == 0 => {
+                Err("Table is empty".into())
+            }
+            SheetKind::Table => Ok(SheetAction::Push(Box::new(self.open_detail_view()))),
+            SheetKind::Workbook { path } => {
+                let sheet = self.selected_name().ok_or("No sheet selected")?;
+                let data = SpreadsheetLoader::load_sheet(path, sheet)?;
+                let app = TuiApp::new(VirtualTable::new(data)).with_title(sheet.to_string());
+                Ok(SheetAction::Push(Box::new(app)))
+            }
+            SheetKind::Database { path } => {
+                let table = self.selected_name().ok_or("No table selected")?;
+                let data = SqliteLoader::load_table(path, table)?;
+                let app = TuiApp::new(VirtualTable::new(data)).with_title(table.to_string());
+                Ok(SheetAction::Push(Box::new(app)))
+            }
+            SheetKind::GroupBy { source, groups, keys } => {
+                if self.selected_row >= self.table.num_rows() {
+                    return Err("No group selected".into());
+                }
+                let row = self.table.data_row(self.selected_row);
+                let rows = &groups[row];
+                // The key columns lead the data, wherever they have been moved to in the view.
+                let data = &self.table.data;
+                let key_values: Vec<String> = (0..*keys)
+                    .map(|col| {
+                        let value = if data.is_null(col, row) {
+                            NULL_DISPLAY
                         } else {
-                            
-                            None
-                        }
-                    }
-                    AggregationFunction::Count => {
-                        Some(column_data.len().to_string())
-                    }
-                    AggregationFunction::UniqueCount => {
-                        let unique_count = column_data.iter().collect::<HashSet<_>>().len();
-                        Some(unique_count.to_string())
-                    }
-                    
-                };
-                agg_results.insert(agg, result);
+                            &data.columns[col][row]
+                        };
+                        format!("{}={}", data.headers[col], value)
+                    })
+                    .collect();
+                let view = VirtualTable::with_rows(Arc::clone(source), Some(rows.clone()));
+                let app = TuiApp::new(view)
+                    .with_title(format!("{} | {}", self.title, key_values.join(", ")));
+                Ok(SheetAction::Push(Box::new(app)))
             }
+            SheetKind::Describe { columns } => {
+                if self.selected_row >= self.table.num_rows() {
+                    return Err("No column selected".into());
+                }
+                Ok(SheetAction::SelectColumn(columns[self.table.data_row(self.selected_row)]))
+            }
+        }
+    }
 
-            if !agg_results.is_empty() {
-                results.insert(col_idx, agg_results);
+    /// Counts the distinct values of the selected column, most frequent first.
+    fn open_frequency(&self) -> TuiApp {
+        let col = self.selected_data_column();
+        let data = &self.table.data;
+        let mut groups = group_rows(data, &[col], self.table.rows().iter().copied());
+        let frequencies = frequency_table(data, col, &mut groups, self.table.num_rows());
+
+        let mut app = TuiApp::new(VirtualTable::new(frequencies))
+            .with_kind(SheetKind::GroupBy {
+                source: Arc::clone(&self.table.data),
+                groups,
+                keys: 1,
+            })
+            .with_title(format!("{} | frequency of {}", self.title, data.headers[col]));
+        app.column_widths = vec![ColumnWidth::Content; 4];
+        app
+    }
+
+    /// Profiles every shown column over the shown rows, a row per column.
+    fn open_describe(&self) -> TuiApp {
+        let columns: Vec<usize> = (0..self.table.num_columns())
+            .map(|col| self.table.data_column(col))
+            .collect();
+        let profile = describe_table(
+            &self.table.data,
+            &columns,
+            self.table.rows(),
+            self.unparseable_policy,
+        );
+        let widths = profile.headers.len();
+        let mut app = TuiApp::new(VirtualTable::new(profile))
+            .with_kind(SheetKind::Describe { columns })
+            .with_title(format!("{} | describe", self.title));
+        app.column_widths = vec![ColumnWidth::Content; widths];
+        app
+    }
+
+    /// Moves the cursor to a data column, unless it is hidden.
+    pub fn select_data_column(&mut self, col: usize) {
+        match (0..self.table.num_columns()).position(|c| self.table.data_column(c) == col) {
+            Some(position) => {
+                self.selected_column = position;
+                self.adjust_horizontal_offset();
+            }
+            None => {
+                self.status_message = Some(format!(
+                    "{} is hidden, gv to show hidden columns",
+                    self.table.data.headers[col]
+                ));
             }
         }
+    }
+
+    /// Summarizes the table by the marked key columns, with the selected
+    /// aggregations computed per group.
+    fn open_group_by(&self) -> Result<TuiApp, Box<dyn Error>> {
+        if self.group_keys.is_empty() {
+            return Err("Mark group key columns with ! first".into());
+        }
+        let data = &self.table.data;
+        let groups = group_rows(data, &self.group_keys, self.table.rows().iter().copied());
+
+        let mut aggregations: Vec<(usize, Vec<AggregationFunction>)> = self
+            .selected_aggregations
+            .iter()
+            .map(|(&col, aggs)| {
+                let mut aggs = aggs.clone();
+                aggs.sort();
+                (col, aggs)
+            })
+            .collect();
+        aggregations.sort_by_key(|(col, _)| *col);
+
+        let (summary, unparseable) = group_table(
+            data,
+            &self.group_keys,
+            &groups,
+            &aggregations,
+            self.unparseable_policy,
+        );
+
+        let key_names: Vec<&str> = self
+            .group_keys
+            .iter()
+            .map(|&col| data.headers[col].as_str())
+            .collect();
+        let columns = summary.headers.len();
+        let mut app = TuiApp::new(VirtualTable::new(summary))
+            .with_kind(SheetKind::GroupBy {
+                source: Arc::clone(&self.table.data),
+                groups,
+                keys: self.group_keys.len(),
+            })
+            .with_title(format!("{} | by {}", self.title, key_names.join(", ")));
+        app.column_widths = vec![ColumnWidth::Content; columns];
+        if unparseable > 0 {
+            app.status_message = Some(format!(
+                "{} unparseable cells ({})",
+                unparseable,
+                self.unparseable_policy.label()
+            ));
+        }
+        Ok(app)
+    }
+
+    /// The first cell of the selected row, which names the entry on list sheets.
+    fn selected_name(&self) -> Option<&str> {
+        (self.table.num_columns() > 0 && self.selected_row < self.table.num_rows())
+            .then(|| self.table.cell(0, self.selected_row))
+    }
 
-        results
+    /// The data column under the cursor, which per-column settings are keyed by.
+    fn selected_data_column(&self) -> usize {
+        self.table.data_column(self.selected_column)
     }
 
     fn open_detail_view(&self) -> TuiApp {
         let selected_row = self.selected_row;
-        let field_column = self.table.data.headers.clone();
-        let value_column: Vec<String> = self
-            .table
-            .data
-            .columns
-            .iter()
-            .map(|col| col[selected_row].clone())
+        let table = &self.table;
+        let columns = 0..table.num_columns();
+        let field_column: Vec<String> =
+            columns.clone().map(|col| table.header(col).to_string()).collect();
+        let value_column: Vec<String> = columns
+            .clone()
+            .map(|col| table.cell(col, selected_row).to_string())
             .collect();
 
-        let detail_data = TableData::new(
+        let value_nulls: Vec<bool> = columns
+            .map(|col| table.is_null(col, selected_row))
+            .collect();
+
+        let detail_data = TableData::with_nulls(
             vec!["Field".to_string(), "Value".to_string()],
             vec![field_column, value_column],
+            vec![Vec::new(), value_nulls],
         );
 
         let detail_table = VirtualTable::new(detail_data);
-        TuiApp::new(detail_table)
+        TuiApp::new(detail_table).with_title(format!("{} | row {}", self.title, selected_row + 1))
     }
 
-    fn sort_table(&mut self, ascending: bool) {
-        let col_idx = self.selected_column;
-        let num_rows = if self.table.data.columns.is_empty() {
-            0
+    /// Switches the selected column to the next type and reparses it.
+    fn cycle_column_type(&mut self) {
+        let col = self.selected_data_column();
+        let column_type = self.table.data.column_type(col).next();
+        let header = &self.table.data.headers[col];
+        self.record_type_change(format!("type {} as {}", header, column_type.label()), col);
+        let failed = self.table.data_mut().set_column_type(col, column_type);
+        self.status_message = (failed > 0).then(|| {
+            format!("{} cells are not {}", failed, column_type.label())
+        });
+        self.invalidate_caches();
+    }
+
+    /// Sorts by the selected column alone, or with `add` by it after the
+    /// keys already in place. Sorting by a key column again changes its
+    /// direction but keeps its priority.
+    fn sort_table(&mut self, ascending: bool, add: bool) {
+        if self.table.num_columns() == 0 {
+            return;
+        }
+        let key = SortKey {
+            column: self.selected_data_column(),
+            ascending,
+        };
+        let direction = if ascending { "ascending" } else { "descending" };
+        let header = &self.table.data.headers[key.column];
+        self.record(if add {
+            format!("then sort by {} {}", header, direction)
+        } else {
+            format!("sort by {} {}", header, direction)
+        });
+        if !add {
+            self.sort_keys.clear();
+        }
+        match self.sort_keys.iter_mut().find(|k| k.column == key.column) {
+            Some(existing) => *existing = key,
+            None => self.sort_keys.push(key),
+        }
+        self.apply_sort();
+    }
+
+    /// Switches how text is ordered and sorts again with it.
+    fn cycle_collation(&mut self) {
+        self.record(format!("text order {}", self.collation.next().label()));
+        self.collation = self.collation.next();
+        self.status_message = Some(format!("Text order: {}", self.collation.label()));
+        if !self.sort_keys.is_empty() {
+            self.apply_sort();
+        }
+    }
+
+    fn apply_sort(&mut self) {
+        self.table.sort_by(&self.sort_keys, self.collation);
+        self.selected_row = 0;
+        // Header widths include the sort indicators.
+        self.content_widths.fill(None);
+    }
+
+    /// Saves the view before an operation for `u` to go back to. Redoing is
+    /// no longer possible once something new is done.
+    ///
+    /// Nothing is saved while rows are still loading, since a saved view
+    /// would lack the rows loaded after it.
+    fn record(&mut self, description: impl Into<String>) {
+        self.push_history(description.into(), None, false);
+    }
+
+    /// Saves the view before an edit, insertion or deletion, along with the
+    /// value of the cell an edit is about to change.
+    fn record_edit(&mut self, description: impl Into<String>, cell: Option<DataChange>) {
+        self.push_history(description.into(), cell, true);
+    }
+
+    /// Saves the view and the type of a data column before the type is changed.
+    fn record_type_change(&mut self, description: impl Into<String>, col: usize) {
+        let data = &self.table.data;
+        let column_type = data.manual_types[col].then(|| data.column_type(col));
+        self.push_history(
+            description.into(),
+            Some(DataChange::ColumnType { col, column_type }),
+            false,
+        );
+    }
+
+    fn push_history(&mut self, description: String, change: Option<DataChange>, edits: bool) {
+        let state = self.view_state(self.undo_stack.last().map(|entry| &entry.state));
+        self.undo_stack.push(HistoryEntry {
+            description,
+            state,
+            change,
+            edits,
+        });
+        if self.undo_stack.len() > HISTORY_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+
+    fn undo(&mut self) {
+        if self.background_load.is_some() {
+            self.status_message = Some("Undo is available once loading finishes".to_string());
+            return;
+        }
+        let Some(entry) = self.undo_stack.pop() else {
+            self.status_message = Some("Nothing to undo".to_string());
+            return;
+        };
+        let entry = self.swap_state(entry);
+        self.status_message = Some(format!("Undid {}", entry.description));
+        self.redo_stack.push(entry);
+    }
+
+    fn redo(&mut self) {
+        if self.background_load.is_some() {
+            self.status_message = Some("Redo is available once loading finishes".to_string());
+            return;
+        }
+        let Some(entry) = self.redo_stack.pop() else {
+            self.status_message = Some("Nothing to redo".to_string());
+            return;
+        };
+        let entry = self.swap_state(entry);
+        self.status_message = Some(format!("Redid {}", entry.description));
+        self.undo_stack.push(entry);
+    }
+
+    /// Shows the view of an entry, which then holds the view shown before.
+    fn swap_state(&mut self, entry: HistoryEntry) -> HistoryEntry {
+        let current = self.view_state(Some(&entry.state));
+        let state = entry.state;
+
+        let change = entry.change.map(|change| self.apply_change(change));
+
+        // A view recorded while loading shows what was loaded after it too,
+        // as it would have had the operation come later.
+        let (loaded_rows, loaded_columns) = state.loaded;
+        let new_rows = loaded_rows..self.load_end.0.max(loaded_rows);
+        let mut rows = Arc::unwrap_or_clone(state.rows);
+        let mut columns = state.columns;
+        columns.extend(loaded_columns..self.load_end.1.max(loaded_columns));
+        self.filter = match state.filter {
+            Some((filter, unfiltered_rows)) => {
+                let mut unfiltered_rows = Arc::unwrap_or_clone(unfiltered_rows);
+                rows.extend(filter.matching_rows(&self.table.data, new_rows.clone()));
+                unfiltered_rows.extend(new_rows.clone());
+                Some(AppliedFilter {
+                    filter,
+                    unfiltered_rows,
+                })
+            }
+            None => {
+                rows.extend(new_rows.clone());
+                None
+            }
+        };
+        self.table.set_rows(rows);
+        self.table.set_columns(columns);
+        self.sort_keys = state.sort_keys;
+        self.collation = state.collation;
+        if !new_rows.is_empty() && !self.sort_keys.is_empty() {
+            self.table.sort_by(&self.sort_keys, self.collation);
+        }
+        self.selected_aggregations = state.selected_aggregations;
+        self.unparseable_policy = state.unparseable_policy;
+        self.group_keys = state.group_keys;
+        self.column_widths = state.column_widths;
+        self.column_widths
+            .resize(self.table.data.headers.len(), ColumnWidth::Fixed(15));
+        self.pinned_columns = state.pinned_columns;
+        (self.selected_row, self.selected_column) = state.cursor;
+        self.unsaved_changes = state.unsaved_changes;
+
+        self.selected_row = self.selected_row.min(self.table.num_rows().saturating_sub(1));
+        self.selected_column = self
+            .selected_column
+            .min(self.table.num_columns().saturating_sub(1));
+        self.invalidate_caches();
+        self.adjust_horizontal_offset();
+
+        HistoryEntry {
+            description: entry.description,
+            state: current,
+            change,
+            edits: entry.edits,
+        }
+    }
+
+    /// Marks the data as saved. States in the history are unsaved from the
+    /// first edit that separates them from it.
+    fn mark_saved(&mut self) {
+        self.unsaved_changes = false;
+        for stack in [&mut self.undo_stack, &mut self.redo_stack] {
+            let mut unsaved = false;
+            for entry in stack.iter_mut().rev() {
+                unsaved |= entry.edits;
+                entry.state.unsaved_changes = unsaved;
+            }
+        }
+    }
+
+    /// Puts back a change to the data and returns the one that undoes it.
+    fn apply_change(&mut self, change: DataChange) -> DataChange {
+        match change {
+            DataChange::Cell { col, row, value } => {
+                let current = self.cell_value(col, row);
+                self.table.data_mut().set_cell(col, row, value);
+                DataChange::Cell {
+                    col,
+                    row,
+                    value: current,
+                }
+            }
+            DataChange::ColumnType { col, column_type } => {
+                let data = self.table.data_mut();
+                let current = data.manual_types[col].then(|| data.column_type(col));
+                match column_type {
+                    Some(column_type) => {
+                        data.set_column_type(col, column_type);
+                    }
+                    None => data.infer_column_type(col),
+                }
+                DataChange::ColumnType {
+                    col,
+                    column_type: current,
+                }
+            }
+        }
+    }
+
+    /// The current view, sharing row lists with `previous` where they match.
+    fn view_state(&self, previous: Option<&ViewState>) -> ViewState {
+        let share = |rows: &[usize], previous: Option<&Arc<Vec<usize>>>| match previous {
+            Some(shared) if shared.as_slice() == rows => Arc::clone(shared),
+            _ => Arc::new(rows.to_vec()),
+        };
+        let previous_filter = previous.and_then(|state| state.filter.as_ref());
+        ViewState {
+            rows: share(self.table.rows(), previous.map(|state| &state.rows)),
+            columns: (0..self.table.num_columns())
+                .map(|col| self.table.data_column(col))
+                .collect(),
+            filter: self.filter.as_ref().map(|applied| {
+                let rows = share(&applied.unfiltered_rows, previous_filter.map(|(_, rows)| rows));
+                (applied.filter.clone(), rows)
+            }),
+            sort_keys: self.sort_keys.clone(),
+            collation: self.collation,
+            selected_aggregations: self.selected_aggregations.clone(),
+            unparseable_policy: self.unparseable_policy,
+            group_keys: self.group_keys.clone(),
+            column_widths: self.column_widths.clone(),
+            pinned_columns: self.pinned_columns,
+            cursor: (self.selected_row, self.selected_column),
+            unsaved_changes: self.unsaved_changes,
+            loaded: (self.table.data.num_rows(), self.table.data.headers.len()),
+        }
+    }
+
+    /// The operations applied to this sheet, oldest first, followed by those
+    /// undone that Ctrl-R would redo.
+    fn open_history(&self) -> TuiApp {
+        let entries = self
+            .undo_stack
+            .iter()
+            .map(|entry| (entry, "applied"))
+            .chain(self.redo_stack.iter().rev().map(|entry| (entry, "undone")));
+        let (mut numbers, mut operations, mut states) = (Vec::new(), Vec::new(), Vec::new());
+        for (i, (entry, state)) in entries.enumerate() {
+            numbers.push((i + 1).to_string());
+            operations.push(entry.description.clone());
+            states.push(state.to_string());
+        }
+        let headers = vec!["#".to_string(), "Operation".to_string(), "State".to_string()];
+        let history = TableData::new(headers, vec![numbers, operations, states]);
+        let mut app = TuiApp::new(VirtualTable::new(history))
+            .with_title(format!("{} | history", self.title));
+        app.column_widths = vec![ColumnWidth::Content; 3];
+        app
+    }
+
+    /// Direction and priority of a data column in the sort, such as "▲1".
+    fn sort_indicator(&self, column: usize) -> Option<String> {
+        let position = self.sort_keys.iter().position(|key| key.column == column)?;
+        let arrow = if self.sort_keys[position].ascending {
+            '▲'
         } else {
-            self.table.data.columns[0].len()
+            '▼'
         };
+        Some(format!("{}{}", arrow, position + 1))
+    }
+}
 
-        let mut indices: Vec<usize> = (0..num_rows).collect();
 
-        indices.sort_by(|&i, &j| {
-            let a = &self.table.data.columns[col_idx][i];
-            let b = &self.table.data.columns[col_idx][j];
-            let ord = compare_cells(a, b);
-            if ascending {
-                ord
-            } else {
-                ord.reverse()
+/// Whether two paths name the same file, comparing them as given when
+/// either does not exist.
+fn same_file(a: &str, b: &str) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => Path::new(a) == Path::new(b),
+    }
+}
+
+
+/// Runs a sheet and the sheets opened from it until the last one is closed.
+/// The terminal should already be set up, in raw mode for crossterm.
+pub fn run<B: Backend>(app: TuiApp, terminal: &mut Terminal<B>) -> Result<(), Box<dyn Error>> {
+    let mut app_stack = vec![app];
+    while let Some(current_app) = app_stack.last_mut() {
+        match current_app.main_loop(terminal)? {
+            SheetAction::Push(mut new_app) => {
+                // Files opened by queries stay at hand on the sheets opened after them.
+                for (name, data) in &current_app.open_tables {
+                    new_app.open_tables.entry(name.clone()).or_insert_with(|| Arc::clone(data));
+                }
+                app_stack.push(*new_app);
             }
-        });
+            SheetAction::Close => {
+                app_stack.pop();
+            }
+            SheetAction::SelectColumn(col) => {
+                app_stack.pop();
+                if let Some(app) = app_stack.last_mut() {
+                    app.select_data_column(col);
+                }
+            }
+            SheetAction::Continue => {}
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet(headers: &[&str], rows: &[&[&str]]) -> TuiApp {
+        let columns = (0..headers.len())
+            .map(|col| rows.iter().map(|row| row[col].to_string()).collect())
+            .collect();
+        let headers = headers.iter().map(|h| h.to_string()).collect();
+        TuiApp::new(VirtualTable::new(TableData::new(headers, columns)))
+    }
 
-        
-        for col in self.table.data.columns.iter_mut() {
-            let reordered_col: Vec<String> = indices.iter().map(|&i| col[i].clone()).collect();
-            *col = reordered_col;
+    fn press(app: &mut TuiApp, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                c => KeyCode::Char(c),
+            };
+            app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
         }
+    }
 
-        
-        self.selected_row = 0;
-        self.table_state.select(Some(self.selected_row));
+    fn column(app: &TuiApp, col: usize) -> Vec<&str> {
+        (0..app.table.num_rows()).map(|row| app.table.cell(col, row)).collect()
+    }
+
+    fn control(app: &mut TuiApp, c: char) {
+        app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL));
+    }
+
+    #[test]
+    fn changing_a_filter_keeps_the_sort() {
+        let mut app = sheet(&["n"], &[&["3"], &["1"], &["4"], &["2"], &["5"]]);
+        press(&mut app, "]fn > 1\n");
+        assert_eq!(column(&app, 0), ["5", "4", "3", "2"]);
+
+        press(&mut app, "f");
+        control(&mut app, 'u');
+        press(&mut app, "n < 5\n");
+        assert_eq!(column(&app, 0), ["4", "3", "2", "1"]);
+
+        press(&mut app, "f");
+        control(&mut app, 'u');
+        press(&mut app, "\n");
+        assert_eq!(column(&app, 0), ["5", "4", "3", "2", "1"]);
+        assert!(app.filter.is_none());
+    }
+
+    #[test]
+    fn operations_while_loading_can_be_undone() {
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let load = BackgroundLoad::spawn(None, move |_, on_chunk| {
+            released.recv()?;
+            let chunk = TableData::new(vec!["n".to_string()], vec![vec!["5".into(), "2".into()]]);
+            on_chunk(chunk);
+            Ok(())
+        });
+        let mut app = sheet(&["n"], &[&["3"], &["1"]]).with_background_load(load);
+        press(&mut app, "]fn > 1\n");
+        assert_eq!(column(&app, 0), ["3"]);
+
+        release.send(()).unwrap();
+        while app.background_load.is_some() {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            app.poll_background_load();
+        }
+        assert_eq!(column(&app, 0), ["5", "3", "2"]);
+        press(&mut app, "u");
+        assert_eq!(column(&app, 0), ["5", "3", "2", "1"]);
+        press(&mut app, "u");
+        assert_eq!(column(&app, 0), ["3", "1", "5", "2"]);
+        control(&mut app, 'r');
+        assert_eq!(column(&app, 0), ["5", "3", "2", "1"]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn only_sheets_with_an_origin_are_edited() {
+        let mut app = sheet(&["n"], &[&["1"], &["2"]]);
+        press(&mut app, "d");
+        assert_eq!(column(&app, 0), ["1", "2"]);
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Only sheets read from a plain delimited file can be edited")
+        );
+
+        let mut app = app.with_origin("n.csv", Dialect::default());
+        press(&mut app, "d");
+        assert_eq!(column(&app, 0), ["2"]);
+    }
+
+    #[test]
+    fn saving_back_keeps_the_order_of_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("n.csv").to_string_lossy().into_owned();
+        std::fs::write(&path, "n\n3\n1\n2\n").unwrap();
+        let mut app =
+            sheet(&["n"], &[&["3"], &["1"], &["2"]]).with_origin(path.as_str(), Dialect::default());
+
+        press(&mut app, "]ae4\n");
+        assert_eq!(column(&app, 0), ["3", "4", "2", "1"]);
+        app.handle_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        press(&mut app, "dfn != 1\n");
+        assert_eq!(column(&app, 0), ["4", "3"]);
+
+        control(&mut app, 's');
+        press(&mut app, "\n\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "n\n3\n4\n1\n");
+        assert!(!app.unsaved_changes);
+    }
+
+    #[test]
+    fn control_keys_do_not_edit() {
+        let mut app = sheet(&["n"], &[&["1"], &["2"]]).with_origin("n.csv", Dialect::default());
+        for c in ['a', 'd', 'e'] {
+            control(&mut app, c);
+        }
+        assert!(app.edit_prompt.is_none());
+        assert_eq!(app.table.data.num_rows(), 2);
+        assert_eq!(column(&app, 0), ["1", "2"]);
+
+        press(&mut app, "d");
+        control(&mut app, 'u');
+        assert_eq!(column(&app, 0), ["2"]);
+        press(&mut app, "u");
+        assert_eq!(column(&app, 0), ["1", "2"]);
+    }
+}
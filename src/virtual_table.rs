@@ -1,13 +1,145 @@
 // src/virtual_table.rs
 
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::column_type::ColumnType;
 use crate::data_loader::TableData;
+use crate::sort::{compare_rows, Collation, SortKey};
 
+/// A view of a table: which of its rows are shown in what order, and which
+/// of its columns. Sorting and filtering rearrange indices, never cells, so
+/// any number of sheets can share one dataset.
+///
+/// Rows and columns are addressed by their position in the view unless a
+/// method says otherwise.
+#[derive(Clone)]
 pub struct VirtualTable {
-    pub data: TableData,
+    pub data: Arc<TableData>,
+    rows: Vec<usize>,
+    columns: Vec<usize>,
 }
 
 impl VirtualTable {
     pub fn new(data: TableData) -> Self {
-        VirtualTable { data }
+        Self::with_rows(Arc::new(data), None)
+    }
+
+    /// A view of shared data showing the given rows, or all of them.
+    pub fn with_rows(data: Arc<TableData>, rows: Option<Vec<usize>>) -> Self {
+        let rows = rows.unwrap_or_else(|| (0..data.num_rows()).collect());
+        let columns = (0..data.headers.len()).collect();
+        VirtualTable { data, rows, columns }
+    }
+
+    /// Mutable access to the underlying data, copying it first if another
+    /// sheet still shares it.
+    pub fn data_mut(&mut self) -> &mut TableData {
+        Arc::make_mut(&mut self.data)
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn num_columns(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// The data row shown at a position.
+    pub fn data_row(&self, row: usize) -> usize {
+        self.rows[row]
+    }
+
+    /// The data column shown at a position.
+    pub fn data_column(&self, col: usize) -> usize {
+        self.columns[col]
+    }
+
+    /// The data rows shown, in view order.
+    pub fn rows(&self) -> &[usize] {
+        &self.rows
+    }
+
+    pub fn header(&self, col: usize) -> &str {
+        &self.data.headers[self.columns[col]]
+    }
+
+    pub fn cell(&self, col: usize, row: usize) -> &str {
+        &self.data.columns[self.columns[col]][self.rows[row]]
+    }
+
+    pub fn is_null(&self, col: usize, row: usize) -> bool {
+        self.data.is_null(self.columns[col], self.rows[row])
+    }
+
+    pub fn column_type(&self, col: usize) -> ColumnType {
+        self.data.column_type(self.columns[col])
+    }
+
+    /// Orders the shown rows by the keys, the first one deciding most. The
+    /// sort is stable, so rows the keys cannot tell apart keep their order.
+    pub fn sort_by(&mut self, keys: &[SortKey], collation: Collation) {
+        let data = &self.data;
+        self.rows
+            .sort_by(|&a, &b| compare_rows(data, keys, collation, a, b));
+    }
+
+    /// Shows exactly these data rows, in this order.
+    pub fn set_rows(&mut self, rows: Vec<usize>) {
+        self.rows = rows;
+    }
+
+    /// Shows exactly these data columns, in this order.
+    pub fn set_columns(&mut self, columns: Vec<usize>) {
+        self.columns = columns;
+    }
+
+    /// Stops showing the column at a position and returns its data column.
+    pub fn hide_column(&mut self, col: usize) -> usize {
+        self.columns.remove(col)
+    }
+
+    /// Shows the hidden data columns again, each right after the one before
+    /// it in the data, and returns how many there were.
+    pub fn unhide_columns(&mut self) -> usize {
+        let mut shown = vec![false; self.data.headers.len()];
+        for &col in &self.columns {
+            shown[col] = true;
+        }
+        let mut count = 0;
+        for col in (0..shown.len()).filter(|&col| !shown[col]) {
+            let position = (0..col)
+                .rev()
+                .find_map(|before| self.columns.iter().position(|&c| c == before))
+                .map_or(0, |position| position + 1);
+            self.columns.insert(position, col);
+            count += 1;
+        }
+        count
+    }
+
+    /// Moves the column at `from` to position `to`, shifting those between.
+    pub fn move_column(&mut self, from: usize, to: usize) {
+        let col = self.columns.remove(from);
+        self.columns.insert(to, col);
     }
-}
\ No newline at end of file
+
+    /// Shows more data rows after the ones already shown.
+    pub fn show_rows(&mut self, rows: impl IntoIterator<Item = usize>) {
+        self.rows.extend(rows);
+    }
+
+    /// Appends a chunk of rows to the data and returns the data rows it
+    /// became, which are not shown until passed to `show_rows`. Columns the
+    /// chunk brings along are added to the view.
+    pub fn append(&mut self, chunk: TableData) -> Range<usize> {
+        let start = self.data.num_rows();
+        let columns_before = self.data.headers.len();
+        let data = self.data_mut();
+        data.append(chunk);
+        let (end, columns_after) = (data.num_rows(), data.headers.len());
+        self.columns.extend(columns_before..columns_after);
+        start..end
+    }
+}